@@ -0,0 +1,154 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+
+use log::trace;
+
+use super::utils::SyncError;
+use super::{utils, Category, Dir, Flavour};
+
+/// Files needed to replay the configure invocation.
+const CONFIG_FILES: [&str; 2] = ["config.status", "config.log"];
+
+pub struct Autotools {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    config: bool,
+}
+
+impl Autotools {
+    /// If only the configuration files are saved.
+    fn config_only(&self) -> bool {
+        self.ignore && self.config
+    }
+
+    /// Copy 'config.status' and 'config.log'.
+    fn dup_config(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            for f in CONFIG_FILES {
+                if d.src_path.join(f).is_file() {
+                    trace!("Backup {:?} of {:?}", f, d.src_path);
+                    utils::cp_r(&d.src_path, &d.target_path, Path::new(f), true)?;
+                }
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+impl Flavour for Autotools {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "autotools-sync", "Sync Autotools build directories");
+        opts.optflag(
+            "",
+            "autotools-config",
+            "Save config.status and config.log of Autotools build directories",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Autotools {
+            dir: Box::new(None),
+            ignore: !args.opt_present("autotools-sync"),
+            config: args.opt_present("autotools-config"),
+        }
+    }
+
+    /// Look for file 'config.status' without 'configure' to identify
+    /// an out-of-tree Autotools build directory, in-tree builds are
+    /// source directories as well.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        let has = |n: &str| d.files.iter().any(|f| f.file_name().unwrap() == n);
+        if has("config.status") && !has("configure") {
+            Some(self.build())
+        } else {
+            None
+        }
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(Autotools {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            config: self.config,
+        })
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Build
+    }
+
+    /// Recurse if --autotools-sync is set.
+    fn recurse(&self) -> bool {
+        !self.ignore
+    }
+
+    /// Skip if --autotools-sync is not set unless the configuration
+    /// shall be saved.
+    fn skip(&self) -> bool {
+        self.ignore && !self.config
+    }
+
+    fn name(&self) -> &'static str {
+        "Autotools"
+    }
+
+    /// Remove an old backup of the build directory if only the
+    /// configuration is saved.
+    fn prepare(&mut self) -> Result<(), SyncError> {
+        let config_only = self.config_only();
+        if let Some(d) = self.dir_mut() {
+            d.ensure_target_path()?;
+            if config_only {
+                utils::rm_dirs_and_files(&d.target_path)?;
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot prepare synchronization without directory".to_string(),
+            ))
+        }
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if self.config_only() {
+            self.dup_config()
+        } else if let Some(d) = self.dir() {
+            d.dup()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if self.config_only() {
+            self.dup_config()
+        } else if let Some(d) = self.dir() {
+            d.merge()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
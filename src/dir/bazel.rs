@@ -0,0 +1,225 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::Path;
+
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour, Git};
+
+/// Files that identify a Bazel, Buck or Pants workspace.
+const WORKSPACE_FILES: [&str; 5] = [
+    "WORKSPACE",
+    "WORKSPACE.bazel",
+    "MODULE.bazel",
+    ".buckconfig",
+    "pants.toml",
+];
+
+/// What the directory is.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    /// Workspace root, 'true' if backed up as Git repository.
+    Workspace(bool),
+    /// Bazel output base outside of the workspace.
+    OutputBase,
+    /// Any directory below.
+    Plain,
+}
+
+pub struct Bazel {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    kind: Kind,
+    /// Template for workspaces that are Git repositories.
+    git: Git,
+}
+
+impl Bazel {
+    fn copy(&self, kind: Kind) -> Bazel {
+        Bazel {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            kind,
+            git: self.git.copy(),
+        }
+    }
+
+    fn dir_unchecked(&self) -> &Dir {
+        match self.dir.as_ref() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
+    /// Record the targets of the output symlinks in 'symlinks'.
+    fn dup_symlinks(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let m = d.target_path.as_path().join("symlinks");
+        if m.exists() {
+            fs::remove_file(&m)?;
+        }
+
+        for e in fs::read_dir(&d.src_path)?.flatten() {
+            let n = e.file_name().to_string_lossy().to_string();
+            if e.file_type()?.is_symlink() && is_output(&d.src_path, &n) {
+                let t = fs::read_link(e.path())?;
+                trace!("Output symlink {:?} points to {:?}", n, t);
+                utils::manifest_add(&m, &["symlink", &n, &t.to_string_lossy()])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Backup the workspace as Git repository or the files in it.
+    fn dup_workspace(&self, merge: bool) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        if self.kind == Kind::Workspace(true) {
+            self.git
+                .dup_nested(d.sub_dir(d.src_path.clone(), d.target_path.clone()))?;
+        } else if merge {
+            d.merge()?;
+        } else {
+            d.dup()?;
+        }
+
+        if let Err(e) = self.dup_symlinks() {
+            d.send_runtime(stats::Info {
+                category: self.category(),
+                name: self.name().to_string(),
+                desc: format!("Failed to record output symlinks because {}", e),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// If entry 'n' of workspace 'p' is an output tree.
+fn is_output(p: &Path, n: &str) -> bool {
+    n.starts_with("bazel-")
+        || n == "buck-out"
+        || n == ".pants.d"
+        || (n == "dist" && p.join("pants.toml").is_file())
+}
+
+impl Flavour for Bazel {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag(
+            "",
+            "bazel-sync",
+            "Sync Bazel, Buck and Pants output directories",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Bazel {
+            dir: Box::new(None),
+            ignore: !args.opt_present("bazel-sync"),
+            kind: Kind::Plain,
+            git: Git::template(args),
+        }
+    }
+
+    /// Look for one of the workspace files or for the file
+    /// 'DO_NOT_BUILD_HERE' of a Bazel output base.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        for f in &d.files {
+            let n = f.file_name().unwrap();
+            if WORKSPACE_FILES.iter().any(|w| n == *w) {
+                return Some(Box::new(self.copy(Kind::Workspace(false))));
+            } else if n == "DO_NOT_BUILD_HERE" {
+                return Some(Box::new(self.copy(Kind::OutputBase)));
+            }
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy(Kind::Plain))
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Build
+    }
+
+    /// Workspaces that are Git repositories are not scanned.
+    fn recurse(&self) -> bool {
+        match self.kind {
+            Kind::Workspace(git) => !git,
+            _ => !self.ignore,
+        }
+    }
+
+    /// Skip output base if --bazel-sync is not set.
+    fn skip(&self) -> bool {
+        self.kind == Kind::OutputBase && self.ignore
+    }
+
+    /// Subdirectories of workspaces are probed.
+    fn stay(&self) -> bool {
+        !matches!(self.kind, Kind::Workspace(_))
+    }
+
+    fn name(&self) -> &'static str {
+        "Bazel"
+    }
+
+    /// Exclude output trees of workspaces unless --bazel-sync is set
+    /// and find out if the workspace is backed up as Git repository,
+    /// with --git-ignore '.git' is excluded.
+    fn prepare(&mut self) -> Result<(), SyncError> {
+        let (ignore, skip_git) = (self.ignore, self.git.skip());
+        let git = !skip_git && !self.git.recurse();
+        let d = match self.dir.as_mut() {
+            Some(d) => d,
+            None => {
+                return Err(SyncError::Failed(
+                    "Cannot prepare synchronization without directory".to_string(),
+                ))
+            }
+        };
+
+        if let Kind::Workspace(_) = self.kind {
+            if ignore {
+                let p = d.src_path.clone();
+                d.dirs
+                    .retain(|e| !is_output(&p, &e.file_name().unwrap().to_string_lossy()));
+            }
+            if skip_git {
+                d.dirs.retain(|e| e.file_name().unwrap() != ".git");
+            }
+            self.kind = Kind::Workspace(git && d.src_path.join(".git").exists());
+        }
+        d.ensure_target_path()
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        match self.kind {
+            Kind::Workspace(_) => self.dup_workspace(false),
+            _ => self.dir_unchecked().dup(),
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        match self.kind {
+            Kind::Workspace(_) => self.dup_workspace(true),
+            _ => self.dir_unchecked().merge(),
+        }
+    }
+}
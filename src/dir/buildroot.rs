@@ -0,0 +1,195 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::PathBuf;
+
+use bitflags::bitflags;
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour};
+
+/// Configuration files that are saved from the output directory.
+const CONFIG_FILES: [&str; 2] = [".config", "defconfig"];
+
+pub struct Buildroot {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    ignore_downloads: bool,
+    ignore_output: bool,
+    /// Top level directory of the Buildroot tree.
+    root: bool,
+    /// Output directories that are excluded.
+    excluded: Vec<PathBuf>,
+}
+
+bitflags! {
+    #[derive(PartialEq)]
+    struct RequiredFiles: u8 {
+        const NONE = 0;
+        const CONFIG_IN = 1;
+        const PACKAGE = 2;
+        const BOARD = 4;
+        const CONFIGS = 8;
+        const ALL = Self::CONFIG_IN.bits() | Self::PACKAGE.bits() | Self::BOARD.bits() | Self::CONFIGS.bits();
+    }
+}
+
+impl Buildroot {
+    fn copy(&self, root: bool) -> Buildroot {
+        Buildroot {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            ignore_downloads: self.ignore_downloads,
+            ignore_output: self.ignore_output,
+            root,
+            excluded: vec![],
+        }
+    }
+
+    /// Save the configuration of excluded output directories.
+    fn dup_configs(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            for e in &self.excluded {
+                for f in CONFIG_FILES {
+                    let p = e.strip_prefix(&d.src_path).unwrap().join(f);
+                    if d.src_path.join(&p).is_file() {
+                        trace!("Backup {:?} of {:?}", f, e);
+                        utils::cp_r_d(&d.src_path, &d.target_path, &p, true)?;
+                    }
+                }
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+impl Flavour for Buildroot {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "buildroot-ignore", "Ignore Buildroot directories");
+        opts.optflag("", "buildroot-dl-sync", "Sync Buildroot download directory");
+        opts.optflag(
+            "",
+            "buildroot-output-sync",
+            "Sync Buildroot output directory",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Buildroot {
+            dir: Box::new(None),
+            ignore: args.opt_present("buildroot-ignore"),
+            ignore_downloads: !args.opt_present("buildroot-dl-sync"),
+            ignore_output: !args.opt_present("buildroot-output-sync"),
+            root: false,
+            excluded: vec![],
+        }
+    }
+
+    /// Look for file 'Config.in' and directories 'package', 'board'
+    /// and 'configs' to identify Buildroot directory.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        let mut m = RequiredFiles::NONE;
+        if d.files
+            .iter()
+            .any(|f| f.file_name().unwrap() == "Config.in")
+        {
+            m |= RequiredFiles::CONFIG_IN;
+        }
+        for d in &d.dirs {
+            let f = d.file_name().unwrap();
+            if f == "package" {
+                m |= RequiredFiles::PACKAGE;
+            } else if f == "board" {
+                m |= RequiredFiles::BOARD;
+            } else if f == "configs" {
+                m |= RequiredFiles::CONFIGS;
+            }
+        }
+        if m == RequiredFiles::ALL {
+            return Some(Box::new(self.copy(true)));
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy(false))
+    }
+
+    fn set_dir(&mut self, mut d: Dir) {
+        if self.root {
+            let (dl, output) = (self.ignore_downloads, self.ignore_output);
+            let (excluded, dirs) = d.dirs.into_iter().partition(|e| {
+                let f = e.file_name().unwrap();
+                (dl && f == "dl") || (output && f == "output")
+            });
+            self.excluded = excluded;
+            d.dirs = dirs;
+        }
+
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Special
+    }
+
+    fn recurse(&self) -> bool {
+        !self.skip()
+    }
+
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn name(&self) -> &'static str {
+        "Buildroot"
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            if let Err(e) = self.dup_configs() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup configuration because {}", e),
+                });
+            }
+            d.dup()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            if let Err(e) = self.dup_configs() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup configuration because {}", e),
+                });
+            }
+            d.merge()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
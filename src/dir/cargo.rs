@@ -40,7 +40,7 @@ impl Flavour for Cargo {
     }
 
     fn set_dir(&mut self, d: Dir) {
-        self.dir = Box::new(Some(d));
+        *self.dir = Some(d);
     }
 
     fn dir(&self) -> &Option<Dir> {
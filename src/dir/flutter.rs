@@ -45,7 +45,7 @@ impl Flavour for Flutter {
     }
 
     fn set_dir(&mut self, d: Dir) {
-        self.dir = Box::new(Some(d));
+        *self.dir = Some(d);
     }
 
     fn dir(&self) -> &Option<Dir> {
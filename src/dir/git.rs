@@ -2,12 +2,19 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::OsStr;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 
 use git2::build::{CloneLocal, RepoBuilder};
 use git2::{
-    Branch, BranchType, Delta, Email, EmailCreateOptions, ObjectType, Repository, Signature,
+    AutotagOption, Branch, BranchType, Buf, Delta, DiffDelta, DiffFindOptions, DiffFormat,
+    DiffOptions, Email, EmailCreateOptions, FetchOptions, FetchPrune, FileMode, ObjectType, Oid,
+    Repository, Revwalk, Signature, StatusOptions, Tree,
 };
 use log::trace;
 
@@ -21,10 +28,37 @@ pub struct Git {
     ignore_stashes: bool,
     ignore_untracked: bool,
     ignore_unstaged: bool,
+    ignore_staged: bool,
+    staged_patch: bool,
     ignore_unpushed: bool,
+    bundle: bool,
+    ignore_lfs: bool,
+    include_ignored: Vec<String>,
+    exclude_ignored: Vec<String>,
+    untracked_max_size: Option<u64>,
 }
 
 impl Git {
+    /// Create new flavour with the same options but without [Dir].
+    pub(crate) fn copy(&self) -> Git {
+        Git {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            full: self.full,
+            ignore_stashes: self.ignore_stashes,
+            ignore_unstaged: self.ignore_unstaged,
+            ignore_untracked: self.ignore_untracked,
+            ignore_staged: self.ignore_staged,
+            staged_patch: self.staged_patch,
+            ignore_unpushed: self.ignore_unpushed,
+            bundle: self.bundle,
+            ignore_lfs: self.ignore_lfs,
+            include_ignored: self.include_ignored.clone(),
+            exclude_ignored: self.exclude_ignored.clone(),
+            untracked_max_size: self.untracked_max_size,
+        }
+    }
+
     fn dir_unchecked(&self) -> &Dir {
         match self.dir.as_ref() {
             Some(d) => d,
@@ -32,6 +66,13 @@ impl Git {
         }
     }
 
+    fn dir_unchecked_mut(&mut self) -> &mut Dir {
+        match self.dir.as_mut() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
     fn subdir_create(&self, n: &str) -> Result<(), SyncError> {
         let d = self.dir_unchecked();
         let p = &d.target_path.as_path().join(n);
@@ -72,10 +113,10 @@ impl Git {
 
         let mut r = Repository::open(&d.src_path)?;
 
-        let mut stashes: Vec<(git2::Oid, String)> = Vec::new();
-        r.stash_foreach(|_, name, id| {
+        let mut stashes: Vec<(usize, git2::Oid, String)> = Vec::new();
+        r.stash_foreach(|i, name, id| {
             trace!("Backup stash named {}", name);
-            stashes.push((*id, name.to_string()));
+            stashes.push((i, *id, name.to_string()));
             true
         })?;
 
@@ -84,8 +125,9 @@ impl Git {
             return Ok(());
         }
 
-        // write one file per stash
-        for (id, name) in stashes {
+        // write one file per stash, the stash index is kept in the
+        // file name so that the order can be restored
+        for (i, id, name) in stashes {
             let c = r
                 .find_object(id, Some(ObjectType::Commit))?
                 .into_commit()
@@ -94,8 +136,10 @@ impl Git {
                 .find_object(c.parent_id(0)?, Some(ObjectType::Commit))?
                 .into_commit()
                 .unwrap();
-            let d = r.diff_tree_to_tree(Some(&c.tree()?), Some(&c_p.tree()?), None)?;
-            let sig = r.signature().or_else(|_| Signature::now("", ""));
+            let d = r.diff_tree_to_tree(Some(&c_p.tree()?), Some(&c.tree()?), None)?;
+            let sig = r
+                .signature()
+                .or_else(|_| Signature::now("devsync", "devsync@localhost"));
             let mail = Email::from_diff(
                 &d,
                 1,
@@ -106,18 +150,91 @@ impl Git {
                 &sig?,
                 &mut EmailCreateOptions::default(),
             )?;
-            let _ = fs::write(p.join(format!("{}-{}", name, id)), mail.as_slice());
+            let _ = fs::write(p.join(format!("{}-{}", i, id)), mail.as_slice());
+        }
+        Ok(())
+    }
+
+    /// Add record to manifest, failures are reported but do not stop
+    /// the backup.
+    fn manifest_record(&self, fields: &[&str]) {
+        let d = self.dir_unchecked();
+        if let Err(e) = utils::manifest_add(&d.target_path.join("manifest"), fields) {
+            d.send_runtime(stats::Info {
+                category: self.category(),
+                name: String::from(self.name()),
+                desc: format!("Failed to add {} to manifest because {}", fields[0], e),
+            });
+        }
+    }
+
+    /// Record deleted, renamed and type changed files of section 'n'
+    /// in manifest, for type changes to symbolic links the link
+    /// target 'link' is recorded as well.
+    fn manifest_delta(&self, n: &str, delta: &DiffDelta, link: Option<&Path>) {
+        let old = delta.old_file().path().unwrap().to_string_lossy();
+        let new = delta.new_file().path().unwrap().to_string_lossy();
+        match delta.status() {
+            Delta::Deleted => self.manifest_record(&["deleted", n, &old]),
+            Delta::Renamed => self.manifest_record(&["renamed", n, &old, &new]),
+            Delta::Typechange => match link {
+                Some(l) => self.manifest_record(&["typechange", n, &new, &l.to_string_lossy()]),
+                None => self.manifest_record(&["typechange", n, &new]),
+            },
+            _ => (),
+        }
+    }
+
+    /// Is untracked file larger than --git-untracked-max-size?
+    fn untracked_too_large(&self, p: &Path) -> bool {
+        match self.untracked_max_size {
+            Some(max) => fs::symlink_metadata(p).is_ok_and(|m| m.len() > max),
+            None => false,
+        }
+    }
+
+    /// Does ignored file match one of the include globs but none of
+    /// the exclude globs?
+    fn ignored_included(&self, p: &Path) -> bool {
+        self.include_ignored.iter().any(|g| utils::glob_match(g, p))
+            && !self.exclude_ignored.iter().any(|g| utils::glob_match(g, p))
+    }
+
+    /// Collect files below ignored directory 'p' that match
+    /// --git-include-ignored, directories that match
+    /// --git-exclude-ignored and nested repositories are not entered.
+    fn ignored_files(
+        &self,
+        p: &Path,
+        files: &mut Vec<(PathBuf, &'static str)>,
+    ) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        if self.exclude_ignored.iter().any(|g| utils::glob_match(g, p))
+            || d.src_path.join(p).join(".git").exists()
+        {
+            trace!("Skip ignored directory {:?}", p);
+            return Ok(());
+        }
+
+        for e in fs::read_dir(d.src_path.join(p))?.flatten() {
+            let f = p.join(e.file_name());
+            if e.file_type()?.is_dir() {
+                self.ignored_files(&f, files)?;
+            } else if self.ignored_included(&f) {
+                files.push((f, "ignored"));
+            }
         }
         Ok(())
     }
 
     /// Copy untracked/unstaged files to backup directory unless
-    /// --git-ignore-untracked or --git-ignore-unstaged are set.
+    /// --git-ignore-untracked or --git-ignore-unstaged are set,
+    /// ignored files only if they match --git-include-ignored.
     fn dup_status(&self) -> Result<(), SyncError> {
         let d = self.dir_unchecked();
         let repo = Repository::open(&d.src_path)?;
         let mut r = Ok(());
-        let mut empty = (true, true); // untracked / unstaged
+        let mut empty = (true, true, true); // untracked / unstaged / ignored
 
         let tp_untracked = Path::new(&d.target_path).join("untracked");
         if self.ignore_untracked {
@@ -135,54 +252,100 @@ impl Git {
             self.subdir_create("unstaged")?;
         }
 
-        for s in repo.statuses(None)?.iter() {
-            if let Some(diff) = s.index_to_workdir() {
-                let p = Path::new(diff.new_file().path().unwrap());
-                match diff.status() {
-                    Delta::Modified if !self.ignore_unstaged => {
-                        trace!("Backup unstaged {:?}", p);
-                        if let Err(e) = utils::cp_r_d(&d.src_path, &tp_unstaged, p, true) {
-                            d.send_runtime(stats::Info {
-                                category: self.category(),
-                                name: String::from(self.name()),
-                                desc: format!(
-                                    "Failed to backup unstaged file {:?} because {}",
-                                    p, e
-                                ),
-                            });
-                            if r.is_ok() {
-                                r = Err(SyncError::Failed(format!(
-                                    "Failed to backup file(s) from {:?}",
-                                    d.src_path
-                                )))
-                            }
-                        } else {
-                            empty.1 = false;
-                        }
-                    }
-                    Delta::Untracked if !self.ignore_untracked => {
-                        trace!("Backup untracked {:?}", p);
-                        if let Err(e) = utils::cp_r_d(&d.src_path, &tp_untracked, p, true) {
-                            d.send_runtime(stats::Info {
-                                category: self.category(),
-                                name: String::from(self.name()),
-                                desc: format!(
-                                    "Failed to backup untracked file {:?} because {}",
-                                    p, e
-                                ),
-                            });
-                            if r.is_ok() {
-                                r = Err(SyncError::Failed(format!(
-                                    "Failed to backup file(s) from {:?}",
-                                    d.src_path
-                                )))
-                            }
-                        } else {
-                            empty.0 = false;
-                        }
+        let tp_ignored = Path::new(&d.target_path).join("ignored");
+        if self.include_ignored.is_empty() {
+            self.subdir_ignored("ignored")?;
+            empty.2 = false;
+        } else {
+            self.subdir_create("ignored")?;
+        }
+
+        // ignored directories are walked by ourselves to leave out
+        // excluded subdirectories
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .renames_index_to_workdir(true)
+            .include_ignored(!self.include_ignored.is_empty());
+
+        let mut files: Vec<(PathBuf, &'static str)> = Vec::new();
+        for s in repo.statuses(Some(&mut opts))?.iter() {
+            let diff = match s.index_to_workdir() {
+                Some(diff) => diff,
+                None => continue,
+            };
+            let p = Path::new(diff.new_file().path().unwrap());
+            let n = match diff.status() {
+                Delta::Modified | Delta::Renamed | Delta::Typechange if !self.ignore_unstaged => {
+                    "unstaged"
+                }
+                Delta::Deleted if !self.ignore_unstaged => {
+                    self.manifest_delta("unstaged", &diff, None);
+                    empty.1 = false;
+                    continue;
+                }
+                Delta::Untracked if !self.ignore_untracked => {
+                    if self.untracked_too_large(&d.src_path.join(p)) {
+                        trace!("Skip large untracked file {:?}", p);
+                        self.manifest_record(&["skipped", "untracked", &p.to_string_lossy()]);
+                        continue;
                     }
-                    _ => (),
+                    "untracked"
+                }
+                Delta::Ignored
+                    if fs::symlink_metadata(d.src_path.join(p)).is_ok_and(|m| m.is_dir()) =>
+                {
+                    self.ignored_files(&p.components().collect::<PathBuf>(), &mut files)?;
+                    continue;
                 }
+                Delta::Ignored if self.ignored_included(p) => "ignored",
+                _ => continue,
+            };
+
+            let link = match diff.new_file().mode() {
+                FileMode::Link => fs::read_link(d.src_path.join(p)).ok(),
+                _ => None,
+            };
+            self.manifest_delta(n, &diff, link.as_deref());
+            if matches!(
+                diff.new_file().mode(),
+                FileMode::Blob | FileMode::BlobExecutable | FileMode::Link
+            ) {
+                files.push((p.to_path_buf(), n));
+            }
+        }
+
+        for (p, n) in files {
+            let tp = match n {
+                "untracked" => &tp_untracked,
+                "unstaged" => &tp_unstaged,
+                _ => &tp_ignored,
+            };
+            let res = if fs::symlink_metadata(d.src_path.join(&p)).is_ok_and(|m| m.is_symlink()) {
+                trace!("Backup {} link {:?}", n, p);
+                utils::ln_r_d(&d.src_path, tp, &p)
+            } else {
+                trace!("Backup {} {:?}", n, p);
+                utils::cp_r_d(&d.src_path, tp, &p, true)
+            };
+            if let Err(e) = res {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: String::from(self.name()),
+                    desc: format!("Failed to backup {} file {:?} because {}", n, p, e),
+                });
+                if r.is_ok() {
+                    r = Err(SyncError::Failed(format!(
+                        "Failed to backup file(s) from {:?}",
+                        d.src_path
+                    )))
+                }
+            } else if n == "untracked" {
+                empty.0 = false;
+            } else if n == "ignored" {
+                empty.2 = false;
+            } else {
+                empty.1 = false;
             }
         }
 
@@ -190,6 +353,10 @@ impl Git {
             self.subdir_empty("untracked")?
         }
 
+        if empty.2 {
+            self.subdir_empty("ignored")?
+        }
+
         if empty.1 {
             self.subdir_empty("unstaged")?
         }
@@ -197,6 +364,114 @@ impl Git {
         r
     }
 
+    /// Copy staged files from the index to backup directory unless
+    /// --git-ignore-staged is set. With --git-staged-patch the staged
+    /// changes are saved as patch against HEAD instead.
+    fn dup_staged(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let tp = Path::new(&d.target_path).join("staged");
+        self.subdir_create("staged")?;
+
+        if self.ignore_staged {
+            self.subdir_ignored("staged")?;
+            return Ok(());
+        }
+
+        let repo = Repository::open(&d.src_path)?;
+        let head = match repo.head() {
+            Ok(h) => Some(h.peel_to_tree()?),
+            Err(_) => None,
+        };
+        let mut diff = repo.diff_tree_to_index(
+            head.as_ref(),
+            None,
+            Some(
+                DiffOptions::new()
+                    .show_binary(true)
+                    .include_typechange(true),
+            ),
+        )?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        for delta in diff.deltas() {
+            // the target of a symbolic link is the content of its blob
+            let link = match delta.new_file().mode() {
+                FileMode::Link => repo
+                    .find_blob(delta.new_file().id())
+                    .ok()
+                    .map(|b| PathBuf::from(OsStr::from_bytes(b.content()))),
+                _ => None,
+            };
+            self.manifest_delta("staged", &delta, link.as_deref());
+        }
+
+        if diff.deltas().len() == 0 {
+            self.subdir_empty("staged")?;
+            return Ok(());
+        }
+
+        if self.staged_patch {
+            trace!("Backup staged changes as patch");
+            let mut patch: Vec<u8> = Vec::new();
+            diff.print(DiffFormat::Patch, |_, _, l| {
+                if matches!(l.origin(), '+' | '-' | ' ') {
+                    patch.push(l.origin() as u8);
+                }
+                patch.extend_from_slice(l.content());
+                true
+            })?;
+            fs::write(tp.join("index.patch"), patch)?;
+            return Ok(());
+        }
+
+        let mut r = Ok(());
+        for delta in diff.deltas() {
+            if !matches!(
+                delta.status(),
+                Delta::Added | Delta::Modified | Delta::Renamed | Delta::Typechange
+            ) || !matches!(
+                delta.new_file().mode(),
+                FileMode::Blob | FileMode::BlobExecutable
+            ) {
+                continue;
+            }
+
+            let f = delta.new_file();
+            let p = tp.join(f.path().unwrap());
+            trace!("Backup staged {:?}", f.path().unwrap());
+            let res = repo
+                .find_blob(f.id())
+                .map_err(SyncError::from)
+                .and_then(|b| {
+                    fs::create_dir_all(p.parent().unwrap())?;
+                    fs::write(&p, b.content())?;
+                    if f.mode() == FileMode::BlobExecutable {
+                        fs::set_permissions(&p, fs::Permissions::from_mode(0o755))?;
+                    }
+                    Ok(())
+                });
+            if let Err(e) = res {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: String::from(self.name()),
+                    desc: format!(
+                        "Failed to backup staged file {:?} because {}",
+                        f.path().unwrap(),
+                        e
+                    ),
+                });
+                if r.is_ok() {
+                    r = Err(SyncError::Failed(format!(
+                        "Failed to backup file(s) from {:?}",
+                        d.src_path
+                    )))
+                }
+            }
+        }
+
+        r
+    }
+
     /// Does local branch has an upstream branch?
     fn branch_upstream(&self, b: &Branch) -> bool {
         let id_new = b.get().target().unwrap();
@@ -208,27 +483,179 @@ impl Git {
         id_old == id_new
     }
 
+    /// Update existing bare repository from the original repository,
+    /// branches that disappeared are pruned.
+    fn update_repo(&self, b: &Repository, rp: &str) -> Result<(), SyncError> {
+        b.remote_set_url("origin", rp)?;
+        b.find_remote("origin")?.fetch(
+            &[] as &[&str],
+            Some(
+                FetchOptions::new()
+                    .prune(FetchPrune::On)
+                    .download_tags(AutotagOption::All),
+            ),
+            None,
+        )?;
+
+        // local branches of the bare repository mirror the remote
+        // tracking branches
+        let mut heads: Vec<(String, Option<Oid>)> = Vec::new();
+        for rf in b.references_glob("refs/heads/*")?.flatten() {
+            let n = rf.name().unwrap().to_string();
+            let ub = n.replacen("refs/heads/", "refs/remotes/origin/", 1);
+            heads.push((n, b.refname_to_id(&ub).ok()));
+        }
+        for (n, id) in heads {
+            match id {
+                Some(id) => {
+                    b.reference(&n, id, true, "devsync update")?;
+                }
+                None => b.find_reference(&n)?.delete()?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetch tags, notes and a detached HEAD into the bare repository,
+    /// a plain clone only has the branches.
+    fn fetch_refs(&self, b: &Repository, r: &Repository) -> Result<(), SyncError> {
+        // fetch from the git directory of the repository, for linked
+        // worktrees this is where the worktree's HEAD lives
+        let mut remote = b.remote_anonymous(r.path().to_str().unwrap())?;
+        let mut specs = vec!["+refs/tags/*:refs/tags/*", "+refs/notes/*:refs/notes/*"];
+        if r.head_detached()? {
+            specs.push("+HEAD:refs/devsync/HEAD");
+        } else if let Ok(mut rf) = b.find_reference("refs/devsync/HEAD") {
+            rf.delete()?;
+        }
+
+        remote.fetch(
+            &specs,
+            Some(
+                FetchOptions::new()
+                    .prune(FetchPrune::On)
+                    .download_tags(AutotagOption::None),
+            ),
+            None,
+        )?;
+
+        Ok(())
+    }
+
     /// Dupliate repository (bare) in case that there are local
     /// branches without upstream branch or if the local and upstream
-    /// branch do not match.
+    /// branch do not match. An existing backup is updated instead.
     fn dup_repo(&self, r: &Repository) -> Result<(), SyncError> {
         let d = self.dir_unchecked();
         let p = &d.target_path.as_path().join("repo");
-        let rp = r.path().parent().unwrap().to_str().unwrap();
+        let cd = common_dir(r);
+        let rp = cd.to_str().unwrap();
+        if p.is_dir() {
+            match Repository::open_bare(p) {
+                Ok(b) => {
+                    trace!("Update repository backup {:?}", p);
+                    self.update_repo(&b, rp)?;
+                    return self.fetch_refs(&b, r);
+                }
+                Err(_) => fs::remove_dir_all(p)?,
+            }
+        }
+
         match RepoBuilder::new()
             .bare(true)
             .clone_local(CloneLocal::Local)
             .clone(rp, p)
         {
-            Ok(_) => Ok(()),
+            Ok(b) => self.fetch_refs(&b, r),
             Err(_) => Err(SyncError::Failed(format!("Cannot clone repository {}", rp))),
         }
     }
 
+    /// Write bundle with the commits that are reachable from local
+    /// branches, tags, notes or a detached HEAD but not from remote
+    /// tracking branches, the remaining history is expected to be
+    /// fetched from upstream.
+    fn dup_bundle(&self, r: &Repository) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let p = d.target_path.as_path().join("repo");
+        if p.exists() {
+            fs::remove_dir_all(&p)?;
+        }
+
+        // references with the commit they point to
+        let mut refs: Vec<(String, Oid, Oid)> = Vec::new();
+        for g in ["refs/heads/*", "refs/tags/*", "refs/notes/*"] {
+            for rf in r.references_glob(g)?.flatten() {
+                if let (Some(n), Some(id), Ok(c)) = (rf.name(), rf.target(), rf.peel_to_commit()) {
+                    refs.push((n.to_string(), id, c.id()));
+                }
+            }
+        }
+        if r.head_detached()? {
+            let c = r.head()?.peel_to_commit()?.id();
+            refs.push(("HEAD".to_string(), c, c));
+        }
+
+        let walk = || -> Result<Revwalk, git2::Error> {
+            let mut walk = r.revwalk()?;
+            for (_, _, c) in &refs {
+                walk.push(*c)?;
+            }
+            walk.hide_glob("refs/remotes/*")?;
+            Ok(walk)
+        };
+        let commits: HashSet<Oid> = walk()?.collect::<Result<_, _>>()?;
+
+        // commits the bundle depends on
+        let mut prereqs: BTreeSet<Oid> = BTreeSet::new();
+        for id in &commits {
+            for pid in r.find_commit(*id)?.parent_ids() {
+                if !commits.contains(&pid) {
+                    prereqs.insert(pid);
+                }
+            }
+        }
+        for (_, _, c) in &refs {
+            if !commits.contains(c) {
+                prereqs.insert(*c);
+            }
+        }
+
+        // objects reachable from remote tracking branches are left out
+        let mut pb = r.packbuilder()?;
+        pb.insert_walk(&mut walk()?)?;
+        // annotated tags
+        for (_, id, c) in &refs {
+            if id != c {
+                pb.insert_object(*id, None)?;
+            }
+        }
+        let mut pack = Buf::new();
+        pb.write_buf(&mut pack)?;
+
+        trace!(
+            "Bundle {} commits with {} prerequisites",
+            commits.len(),
+            prereqs.len()
+        );
+        let mut b: Vec<u8> = Vec::new();
+        writeln!(b, "# v2 git bundle")?;
+        for id in prereqs {
+            writeln!(b, "-{}", id)?;
+        }
+        for (n, id, _) in refs {
+            writeln!(b, "{} {}", id, n)?;
+        }
+        writeln!(b)?;
+        b.extend_from_slice(&pack);
+        fs::write(d.target_path.as_path().join("repo.bundle"), b)?;
+
+        Ok(())
+    }
+
     /// Check if bare repository clone is required.
     fn dup_local(&self) -> Result<(), SyncError> {
-        self.subdir_create("repo")?;
-
         if self.ignore_unpushed {
             self.subdir_ignored("repo")?;
             return Ok(());
@@ -248,7 +675,33 @@ impl Git {
             }
         }
 
-        if upd {
+        // tags, notes and a detached HEAD have no upstream, they need
+        // a backup if remote tracking branches do not contain them
+        if !upd {
+            let mut walk = r.revwalk()?;
+            for g in ["refs/tags/*", "refs/notes/*"] {
+                for rf in r.references_glob(g)?.flatten() {
+                    // tags may point to trees or blobs, there is
+                    // nothing to push
+                    if let Ok(c) = rf.peel_to_commit() {
+                        walk.push(c.id())?;
+                    }
+                }
+            }
+            if r.head_detached()? {
+                walk.push(r.head()?.peel_to_commit()?.id())?;
+            }
+            walk.hide_glob("refs/remotes/*")?;
+            if let Some(id) = walk.next() {
+                trace!("Commit {} not pushed", id?);
+                upd = true;
+            }
+        }
+
+        if upd && self.bundle {
+            trace!("Backup unpushed commits as bundle");
+            self.dup_bundle(&r)?;
+        } else if upd {
             trace!("Backup repository");
             self.dup_repo(&r)?;
         } else {
@@ -258,10 +711,255 @@ impl Git {
         Ok(())
     }
 
+    /// Collect the LFS objects the pointer files in 'tree' refer to,
+    /// only blobs that differ from 'parent' are checked.
+    fn lfs_tree_oids(
+        &self,
+        r: &Repository,
+        parent: Option<&Tree>,
+        tree: &Tree,
+        oids: &mut BTreeSet<String>,
+    ) -> Result<(), SyncError> {
+        let diff = r.diff_tree_to_tree(parent, Some(tree), None)?;
+        for delta in diff.deltas() {
+            let f = delta.new_file();
+            if f.size() > LFS_POINTER_MAX || !f.exists() {
+                continue;
+            }
+            if let Some(oid) = lfs_pointer_oid(r.find_blob(f.id())?.content()) {
+                oids.insert(oid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy local LFS objects that are referenced by staged files or by
+    /// commits which are not reachable from remote tracking branches
+    /// to 'lfs', the layout follows 'lfs/objects'. Objects from a
+    /// previous backup are kept, those no longer referenced are
+    /// removed.
+    fn dup_lfs(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let t = d.target_path.as_path().join("lfs");
+        let r = Repository::open(&d.src_path)?;
+        let s = common_dir(&r).join("lfs").join("objects");
+        if !s.is_dir() {
+            trace!("Repository does not use LFS");
+            if t.exists() {
+                fs::remove_dir_all(&t)?;
+            }
+            return Ok(());
+        }
+        if self.ignore_lfs {
+            return self.subdir_ignored("lfs");
+        }
+
+        let mut oids: BTreeSet<String> = BTreeSet::new();
+
+        // staged pointers that differ from HEAD
+        let head = r.head().ok().map(|h| h.peel_to_tree()).transpose()?;
+        let diff = r.diff_tree_to_index(head.as_ref(), None, None)?;
+        for delta in diff.deltas() {
+            let f = delta.new_file();
+            if f.size() > LFS_POINTER_MAX || !f.exists() {
+                continue;
+            }
+            if let Ok(b) = r.find_blob(f.id()) {
+                if let Some(oid) = lfs_pointer_oid(b.content()) {
+                    oids.insert(oid);
+                }
+            }
+        }
+
+        let mut walk = r.revwalk()?;
+        for g in ["refs/heads/*", "refs/tags/*"] {
+            for rf in r.references_glob(g)?.flatten() {
+                if let Ok(c) = rf.peel_to_commit() {
+                    walk.push(c.id())?;
+                }
+            }
+        }
+        if r.head_detached()? {
+            walk.push_head()?;
+        }
+        walk.hide_glob("refs/remotes/*")?;
+        for id in walk {
+            let c = r.find_commit(id?)?;
+            let parent = c.parents().next().map(|p| p.tree()).transpose()?;
+            self.lfs_tree_oids(&r, parent.as_ref(), &c.tree()?, &mut oids)?;
+        }
+
+        let files: Vec<PathBuf> = oids
+            .iter()
+            .map(|oid| Path::new(&oid[0..2]).join(&oid[2..4]).join(oid))
+            .collect();
+        fs::create_dir_all(&t)?;
+        prune_dirs(&t, &files.iter().map(|f| t.join(f)).collect::<Vec<_>>())?;
+        let mut n = 0;
+        for (oid, f) in oids.iter().zip(files) {
+            if t.join(&f).is_file() {
+                n += 1;
+            } else if s.join(&f).is_file() {
+                utils::cp_r_d(&s, &t, &f, true)?;
+                n += 1;
+            } else {
+                trace!("LFS object {} not available locally", oid);
+            }
+        }
+
+        if n == 0 {
+            self.subdir_empty("lfs")?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy repository local configuration, hooks, excludes and
+    /// sparse-checkout patterns to 'meta', the layout follows the
+    /// git directory.
+    fn dup_meta(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let r = Repository::open(&d.src_path)?;
+        let cd = common_dir(&r);
+        let t = d.target_path.as_path().join("meta");
+        self.subdir_create("meta")?;
+
+        let mut files: Vec<(PathBuf, PathBuf)> = vec![
+            (cd.clone(), PathBuf::from("config")),
+            (cd.clone(), PathBuf::from("info/exclude")),
+            (
+                r.path().to_path_buf(),
+                PathBuf::from("info/sparse-checkout"),
+            ),
+        ];
+        if let Ok(entries) = fs::read_dir(cd.join("hooks")) {
+            for e in entries.flatten() {
+                if e.path().is_file() && e.path().extension() != Some(OsStr::new("sample")) {
+                    files.push((cd.clone(), Path::new("hooks").join(e.file_name())));
+                }
+            }
+        }
+
+        for (s, f) in files {
+            if s.join(&f).is_file() {
+                utils::cp_r_d(&s, &t, &f, true)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write manifest with the checked out branch and the remotes,
+    /// this is what a restore needs to recreate the repository.
+    fn dup_manifest(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let m = d.target_path.as_path().join("manifest");
+        let r = Repository::open(&d.src_path)?;
+
+        if let Ok(h) = r.head() {
+            if h.is_branch() {
+                utils::manifest_add(&m, &["head", h.shorthand().unwrap_or_default()])?;
+            } else if let Some(id) = h.target() {
+                utils::manifest_add(&m, &["detached", &id.to_string()])?;
+            }
+            if let Some(id) = h.target() {
+                utils::manifest_add(&m, &["oid", &id.to_string()])?;
+            }
+        }
+
+        for n in r.remotes()?.iter().flatten() {
+            if let Some(u) = r.find_remote(n)?.url() {
+                trace!("Remote {} has url {}", n, u);
+                utils::manifest_add(&m, &["remote", n, u])?;
+            }
+        }
+
+        // the remote tracking branch may not exist in a restored
+        // repository, record the configuration instead
+        let cfg = r.config()?;
+        for (b, _) in r.branches(Some(BranchType::Local))?.flatten() {
+            if let Ok(Some(n)) = b.name() {
+                if let (Ok(rn), Ok(mr)) = (
+                    cfg.get_string(&format!("branch.{}.remote", n)),
+                    cfg.get_string(&format!("branch.{}.merge", n)),
+                ) {
+                    utils::manifest_add(&m, &["upstream", n, &rn, &mr])?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run all duplicate steps for the repository in 'd' with the
+    /// options of this flavour, used for submodules and by flavours
+    /// that embed a Git repository.
+    pub(crate) fn dup_nested(&self, d: Dir) -> Result<(), SyncError> {
+        let mut g = self.copy();
+        g.set_dir(d);
+        g.dir_unchecked_mut().ensure_target_path()?;
+        g.dup_all()
+    }
+
+    /// Run all duplicate steps for each initialized submodule, the
+    /// backup goes to 'submodules' with the submodule path appended.
+    fn dup_submodules(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let tp = d.target_path.as_path().join("submodules");
+        let r = Repository::open(&d.src_path)?;
+
+        let mut targets = Vec::new();
+        for sm in r.submodules()? {
+            let sp = d.src_path.as_path().join(sm.path());
+            if Repository::open(&sp).is_err() {
+                trace!("Submodule {:?} not initialized", sp);
+                continue;
+            }
+
+            trace!("Backup submodule {:?}", sp);
+            if let Err(e) = self.dup_nested(d.sub_dir(sp, tp.join(sm.path()))) {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup submodule {:?} because {}", sm.path(), e),
+                });
+            }
+            targets.push(tp.join(sm.path()));
+        }
+
+        // remove backups of submodules that are gone
+        if tp.exists() {
+            prune_dirs(&tp, &targets)?;
+        }
+
+        Ok(())
+    }
+
     /// Run all duplicate steps.
     fn dup_all(&self) -> Result<(), SyncError> {
         if let Some(d) = self.dir() {
-            utils::rm_dirs_and_files(d.target_path.as_path())?;
+            // keep the bare repositories and LFS objects, they are
+            // updated incrementally
+            utils::rm_dirs_and_files_except(
+                d.target_path.as_path(),
+                &["repo", "submodules", "lfs"],
+            )?;
+
+            if let Err(e) = self.dup_manifest() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to write manifest because {}", e),
+                });
+            }
+
+            if let Err(e) = self.dup_meta() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup meta data because {}", e),
+                });
+            }
 
             if let Err(e) = self.dup_stashes() {
                 d.send_runtime(stats::Info {
@@ -271,6 +969,14 @@ impl Git {
                 });
             }
 
+            if let Err(e) = self.dup_staged() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup staged files because {}", e),
+                });
+            }
+
             if let Err(e) = self.dup_status() {
                 d.send_runtime(stats::Info {
                     category: self.category(),
@@ -286,6 +992,22 @@ impl Git {
                     desc: format!("Failed to backup locals because {}", e),
                 });
             }
+
+            if let Err(e) = self.dup_lfs() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup LFS objects because {}", e),
+                });
+            }
+
+            if let Err(e) = self.dup_submodules() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup submodules because {}", e),
+                });
+            }
             Ok(())
         } else {
             Err(SyncError::Failed(
@@ -295,6 +1017,51 @@ impl Git {
     }
 }
 
+/// LFS pointer files are small, larger blobs are not checked.
+const LFS_POINTER_MAX: u64 = 1024;
+
+/// Get the SHA-256 object id from the contents of a LFS pointer file.
+fn lfs_pointer_oid(b: &[u8]) -> Option<String> {
+    let s = std::str::from_utf8(b).ok()?;
+    if !s.starts_with("version https://git-lfs.github.com/spec/") {
+        return None;
+    }
+
+    let oid = s.lines().find_map(|l| l.strip_prefix("oid sha256:"))?;
+    if oid.len() == 64 && oid.bytes().all(|c| c.is_ascii_hexdigit()) {
+        Some(oid.to_string())
+    } else {
+        None
+    }
+}
+
+/// Path of the repository that holds objects and refs, linked
+/// worktrees only have a pointer to it in their git directory.
+fn common_dir(r: &Repository) -> PathBuf {
+    match fs::read_to_string(r.path().join("commondir")) {
+        Ok(c) => r.path().join(c.trim()),
+        Err(_) => r.path().to_path_buf(),
+    }
+}
+
+/// Remove all entries from 'p' that are neither one of 'keep' nor
+/// on the way to one of them.
+fn prune_dirs(p: &Path, keep: &[PathBuf]) -> Result<(), SyncError> {
+    for e in fs::read_dir(p)?.flatten() {
+        let ep = e.path();
+        if keep.contains(&ep) {
+            continue;
+        } else if e.file_type()?.is_dir() && keep.iter().any(|k| k.starts_with(&ep)) {
+            prune_dirs(&ep, keep)?;
+        } else if e.file_type()?.is_dir() {
+            fs::remove_dir_all(&ep)?;
+        } else {
+            fs::remove_file(&ep)?;
+        }
+    }
+    Ok(())
+}
+
 impl Flavour for Git {
     fn init_opts(opts: &mut getopts::Options) {
         opts.optflag("", "git-ignore", "Ignore Git repositories");
@@ -302,7 +1069,37 @@ impl Flavour for Git {
         opts.optflag("", "git-ignore-stashes", "Don't backup stashes");
         opts.optflag("", "git-ignore-unstaged", "Don't backup unstaged files");
         opts.optflag("", "git-ignore-untracked", "Don't backup untracked files");
+        opts.optflag("", "git-ignore-staged", "Don't backup staged files");
+        opts.optflag(
+            "",
+            "git-staged-patch",
+            "Backup staged changes as patch against HEAD",
+        );
         opts.optflag("", "git-ignore-unpushed", "Don't backup unpushed branches");
+        opts.optflag(
+            "",
+            "git-bundle",
+            "Backup unpushed commits as bundle instead of bare clone",
+        );
+        opts.optflag("", "git-ignore-lfs", "Don't backup local Git LFS objects");
+        opts.optopt(
+            "",
+            "git-include-ignored",
+            "Backup ignored files matching one of the globs",
+            "LIST_OF_GLOBS",
+        );
+        opts.optopt(
+            "",
+            "git-exclude-ignored",
+            "Don't backup ignored files matching one of the globs",
+            "LIST_OF_GLOBS",
+        );
+        opts.optopt(
+            "",
+            "git-untracked-max-size",
+            "Don't backup untracked files larger than size (suffix K, M or G)",
+            "SIZE",
+        );
     }
 
     fn template(args: &getopts::Matches) -> Self {
@@ -313,34 +1110,51 @@ impl Flavour for Git {
             ignore_stashes: args.opt_present("git-ignore-stashes"),
             ignore_unstaged: args.opt_present("git-ignore-unstaged"),
             ignore_untracked: args.opt_present("git-ignore-untracked"),
+            ignore_staged: args.opt_present("git-ignore-staged"),
+            staged_patch: args.opt_present("git-staged-patch"),
             ignore_unpushed: args.opt_present("git-ignore-unpushed"),
+            bundle: args.opt_present("git-bundle"),
+            ignore_lfs: args.opt_present("git-ignore-lfs"),
+            include_ignored: match args.opt_str("git-include-ignored") {
+                Some(a) => a.split(',').map(|g| g.trim().to_string()).collect(),
+                _ => vec![],
+            },
+            exclude_ignored: match args.opt_str("git-exclude-ignored") {
+                Some(a) => a.split(',').map(|g| g.trim().to_string()).collect(),
+                _ => vec![],
+            },
+            // validated when the arguments are parsed
+            untracked_max_size: args
+                .opt_str("git-untracked-max-size")
+                .and_then(|s| utils::parse_size(&s)),
         }
     }
 
-    /// Probe for '.git' directory to identify Git repository.
+    /// Probe for '.git' directory to identify Git repository. Linked
+    /// worktrees and submodules have a '.git' file that points to
+    /// the repository instead.
     fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
         for d in &d.dirs {
             if d.file_name().unwrap() == ".git" {
                 return Some(self.build());
             }
         }
+        for f in &d.files {
+            if f.file_name().unwrap() == ".git"
+                && fs::read_to_string(f).is_ok_and(|s| s.starts_with("gitdir:"))
+            {
+                return Some(self.build());
+            }
+        }
         None
     }
 
     fn build(&self) -> Box<dyn Flavour + Send + Sync> {
-        Box::new(Git {
-            dir: Box::new(None),
-            ignore: self.ignore,
-            full: self.full,
-            ignore_stashes: self.ignore_stashes,
-            ignore_unstaged: self.ignore_unstaged,
-            ignore_untracked: self.ignore_untracked,
-            ignore_unpushed: self.ignore_unpushed,
-        })
+        Box::new(self.copy())
     }
 
     fn set_dir(&mut self, d: Dir) {
-        self.dir = Box::new(Some(d));
+        *self.dir = Some(d);
     }
 
     fn dir(&self) -> &Option<Dir> {
@@ -397,3 +1211,481 @@ impl Flavour for Git {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::Config;
+    use git2::{Diff, RepositoryInitOptions};
+    use std::sync::Arc;
+
+    /// Scratch path 'n' for tests.
+    pub(crate) fn path(n: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(n)
+    }
+
+    /// Write file 'f' with content 'c' below 'p'.
+    pub(crate) fn write(p: &Path, f: &str, c: &str) {
+        let f = p.join(f);
+        fs::create_dir_all(f.parent().unwrap()).expect("Failed to create path");
+        fs::write(f, c).expect("Failed to write file");
+    }
+
+    /// Write 'files' to the working copy and stage them.
+    pub(crate) fn stage(r: &Repository, files: &[(&str, &str)]) {
+        let mut i = r.index().unwrap();
+        for (f, c) in files {
+            write(r.workdir().unwrap(), f, c);
+            i.add_path(Path::new(f)).unwrap();
+        }
+        i.write().unwrap();
+    }
+
+    /// Write 'files' to the working copy, stage and commit them.
+    pub(crate) fn commit(r: &Repository, files: &[(&str, &str)], m: &str) -> Oid {
+        stage(r, files);
+        let sig = Signature::now("devsync", "devsync@localhost").unwrap();
+        let tree = r
+            .find_tree(r.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let parent = r.head().ok().map(|h| h.peel_to_commit().unwrap());
+        r.commit(
+            Some("HEAD"),
+            &sig,
+            &sig,
+            m,
+            &tree,
+            parent.as_ref().into_iter().collect::<Vec<_>>().as_slice(),
+        )
+        .unwrap()
+    }
+
+    /// Create repository 'p' on branch 'main' with 'files' committed.
+    pub(crate) fn init_repo(p: &Path, files: &[(&str, &str)]) -> Repository {
+        let _ = fs::remove_dir_all(p);
+        let r = Repository::init_opts(p, RepositoryInitOptions::new().initial_head("main"))
+            .expect("Failed to create repository");
+        commit(&r, files, "init");
+        r
+    }
+
+    /// Clone repository 'u' to 'p'.
+    pub(crate) fn clone_repo(u: &Path, p: &Path) -> Repository {
+        let _ = fs::remove_dir_all(p);
+        Repository::clone(u.to_str().unwrap(), p).expect("Failed to clone repository")
+    }
+
+    /// Backup repository 'p' to 't' with the Git options 'args', no
+    /// runtime errors must be reported.
+    pub(crate) fn backup(p: &Path, t: &Path, args: &[&str]) {
+        let mut opts = getopts::Options::new();
+        Git::init_opts(&mut opts);
+        let cfg = Arc::new(Config {
+            jobs: 1,
+            delete: false,
+            archive: false,
+            owned: false,
+            ignore: vec![],
+        });
+        let stats = stats::Stats::default();
+
+        let mut g = Git::template(&opts.parse(args).unwrap());
+        g.set_dir(
+            Dir::new(0, cfg, stats.sender().clone())
+                .set_src_path(p.to_path_buf())
+                .set_target_path(t.to_path_buf()),
+        );
+        g.prepare().expect("Failed to prepare synchronization");
+        g.dup().expect("Failed to backup repository");
+
+        let errors: Vec<String> = stats
+            .chn
+            .1
+            .try_iter()
+            .filter_map(|t| t.info.map(|i| i.desc))
+            .collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_lfs_pointer_oid() {
+        let oid = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+        let p = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize 12345\n",
+            oid
+        );
+        assert_eq!(lfs_pointer_oid(p.as_bytes()), Some(oid.to_string()));
+        assert_eq!(lfs_pointer_oid(b"oid sha256:1234\n"), None);
+        assert_eq!(
+            lfs_pointer_oid(b"version https://git-lfs.github.com/spec/v1\noid sha256:xyz\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dup_staged() {
+        let p = path("git_staged");
+        let r = init_repo(&p.join("src"), &[("a", "a\n"), ("b", "b\n")]);
+        stage(&r, &[("a", "a\nstaged\n"), ("dir/c", "c\n")]);
+        write(r.workdir().unwrap(), "a", "a\nstaged\nunstaged\n");
+
+        backup(&p.join("src"), &p.join("copies"), &[]);
+        let t = p.join("copies").join("staged");
+        assert_eq!(fs::read_to_string(t.join("a")).unwrap(), "a\nstaged\n");
+        assert_eq!(fs::read_to_string(t.join("dir/c")).unwrap(), "c\n");
+        assert!(!t.join("b").exists());
+
+        backup(&p.join("src"), &p.join("patch"), &["--git-staged-patch"]);
+        let t = p.join("patch").join("staged");
+        let d = Diff::from_buffer(&fs::read(t.join("index.patch")).unwrap()).unwrap();
+        let mut files: Vec<_> = d
+            .deltas()
+            .map(|d| (d.new_file().path().unwrap().to_path_buf(), d.status()))
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            files,
+            [
+                (PathBuf::from("a"), Delta::Modified),
+                (PathBuf::from("dir/c"), Delta::Added),
+            ]
+        );
+        assert!(!t.join("a").exists());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_untracked_link() {
+        let p = path("git_untracked_link");
+        let r = init_repo(&p.join("src"), &[("a", "a\n")]);
+        write(r.workdir().unwrap(), "dir/b", "b\n");
+        std::os::unix::fs::symlink("../a", p.join("src/dir/l")).unwrap();
+        std::os::unix::fs::symlink("/nonexistent", p.join("src/dangling")).unwrap();
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let t = p.join("backup").join("untracked");
+        assert!(t.join("dir/b").is_file());
+        assert_eq!(fs::read_link(t.join("dir/l")).unwrap(), Path::new("../a"));
+        assert_eq!(
+            fs::read_link(t.join("dangling")).unwrap(),
+            Path::new("/nonexistent")
+        );
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_manifest() {
+        let p = path("git_manifest");
+        let files = [("a", "a\n"), ("b", "b\n"), ("c", "c\n"), ("d", "d\n")];
+        let r = init_repo(&p.join("src"), &files);
+        let wd = r.workdir().unwrap();
+
+        // staged rename of 'b' and removal of 'c'
+        let mut i = r.index().unwrap();
+        i.remove_path(Path::new("b")).unwrap();
+        i.remove_path(Path::new("c")).unwrap();
+        i.write().unwrap();
+        fs::rename(wd.join("b"), wd.join("b2")).unwrap();
+        fs::remove_file(wd.join("c")).unwrap();
+        stage(&r, &[("b2", "b\n")]);
+
+        // unstaged removal of 'a' and 'd' replaced by a link
+        fs::remove_file(wd.join("a")).unwrap();
+        fs::remove_file(wd.join("d")).unwrap();
+        std::os::unix::fs::symlink("b2", wd.join("d")).unwrap();
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let m = utils::manifest_read(&p.join("backup").join("manifest")).unwrap();
+        for e in [
+            &["head", "main"][..],
+            &["renamed", "staged", "b", "b2"],
+            &["deleted", "staged", "c"],
+            &["deleted", "unstaged", "a"],
+            &["typechange", "unstaged", "d", "b2"],
+        ] {
+            assert!(m.iter().any(|r| r == e), "{:?} not in {:?}", e, m);
+        }
+        assert!(!m.iter().any(|r| r[0] == "deleted" && r[2] == "b"));
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_bundle() {
+        let p = path("git_bundle");
+        init_repo(&p.join("upstream"), &[("a", "a\n")]);
+        let r = clone_repo(&p.join("upstream"), &p.join("src"));
+        let main = commit(&r, &[("b", "b\n")], "local");
+        let c = r.find_commit(main).unwrap();
+        r.branch("feature", &c, false).unwrap();
+        r.set_head("refs/heads/feature").unwrap();
+        let feature = commit(&r, &[("c", "c\n")], "feature");
+        let sig = Signature::now("devsync", "devsync@localhost").unwrap();
+        let tag = r.tag("v1", c.as_object(), &sig, "v1", false).unwrap();
+
+        backup(&p.join("src"), &p.join("backup"), &["--git-bundle"]);
+        let b = p.join("backup").join("repo.bundle");
+        assert!(!p.join("backup").join("repo").exists());
+
+        // unbundle into a fresh clone of upstream
+        let u = clone_repo(&p.join("upstream"), &p.join("unbundle"));
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .current_dir(u.workdir().unwrap())
+                .args(args)
+                .output()
+                .expect("Failed to run git")
+                .status
+                .success()
+        };
+        assert!(git(&["bundle", "verify", b.to_str().unwrap()]));
+        assert!(git(&[
+            "fetch",
+            b.to_str().unwrap(),
+            "refs/heads/*:refs/bundle/*",
+            "refs/tags/*:refs/tags/*",
+        ]));
+        assert_eq!(u.refname_to_id("refs/bundle/main").unwrap(), main);
+        assert_eq!(u.refname_to_id("refs/bundle/feature").unwrap(), feature);
+        assert_eq!(u.refname_to_id("refs/tags/v1").unwrap(), tag);
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_repo_update() {
+        let p = path("git_repo_update");
+        let r = init_repo(&p.join("src"), &[("a", "a\n")]);
+        let c = r.head().unwrap().peel_to_commit().unwrap();
+        r.branch("gone", &c, false).unwrap();
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let bp = p.join("backup").join("repo");
+        let b = Repository::open_bare(&bp).unwrap();
+        assert_eq!(b.refname_to_id("refs/heads/main").unwrap(), c.id());
+        assert!(b.find_reference("refs/remotes/origin/gone").is_ok());
+        // the backup is updated, not cloned again
+        fs::write(bp.join("marker"), "").unwrap();
+
+        let id = commit(&r, &[("b", "b\n")], "second");
+        r.find_branch("gone", BranchType::Local)
+            .unwrap()
+            .delete()
+            .unwrap();
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        assert!(bp.join("marker").exists());
+        let b = Repository::open_bare(&bp).unwrap();
+        assert_eq!(b.refname_to_id("refs/heads/main").unwrap(), id);
+        assert!(b.find_reference("refs/remotes/origin/gone").is_err());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_submodule_worktree() {
+        let p = path("git_submodule_worktree");
+        init_repo(&p.join("upstream"), &[("a", "a\n")]);
+        let r = init_repo(&p.join("src"), &[("b", "b\n")]);
+        let mut sm = r
+            .submodule(p.join("upstream").to_str().unwrap(), Path::new("sub"), true)
+            .unwrap();
+        sm.clone(None).unwrap();
+        sm.add_finalize().unwrap();
+        commit(&r, &[], "add submodule");
+        let s = sm.open().unwrap();
+        write(s.workdir().unwrap(), "untracked", "u\n");
+        let id = commit(&s, &[("c", "c\n")], "local");
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let t = p.join("backup").join("submodules").join("sub");
+        assert!(t.join("untracked").join("untracked").is_file());
+        let b = Repository::open_bare(t.join("repo")).unwrap();
+        assert!(b.find_commit(id).is_ok());
+
+        // backups of removed submodules are pruned
+        fs::remove_dir_all(p.join("src").join(".git").join("modules")).unwrap();
+        fs::remove_dir_all(p.join("src").join("sub")).unwrap();
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        assert!(!t.exists());
+
+        // linked worktree with its own checked out branch
+        let c = r.head().unwrap().peel_to_commit().unwrap();
+        let wb = r.branch("wt", &c, false).unwrap();
+        r.worktree(
+            "wt",
+            &p.join("wt"),
+            Some(git2::WorktreeAddOptions::new().reference(Some(wb.get()))),
+        )
+        .unwrap();
+        let w = Repository::open(p.join("wt")).unwrap();
+        let id = commit(&w, &[("d", "d\n")], "worktree");
+        write(&p.join("wt"), "untracked", "w\n");
+
+        backup(&p.join("wt"), &p.join("wt.backup"), &[]);
+        let t = p.join("wt.backup");
+        assert!(t.join("untracked").join("untracked").is_file());
+        let m = utils::manifest_read(&t.join("manifest")).unwrap();
+        assert!(m.iter().any(|e| e == &["head", "wt"]));
+        let b = Repository::open_bare(t.join("repo")).unwrap();
+        assert!(b.find_commit(id).is_ok());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_notes() {
+        let p = path("git_notes");
+        init_repo(&p.join("upstream"), &[("a", "a\n")]);
+        let r = clone_repo(&p.join("upstream"), &p.join("src"));
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        assert!(p.join("backup").join("repo.empty").exists());
+
+        let sig = Signature::now("devsync", "devsync@localhost").unwrap();
+        let head = r.head().unwrap().target().unwrap();
+        let id = r.note(&sig, &sig, None, head, "note", false).unwrap();
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let b = Repository::open_bare(p.join("backup").join("repo")).unwrap();
+        assert_eq!(b.find_note(None, head).unwrap().id(), id);
+
+        // changed notes update the backup
+        let id = r.note(&sig, &sig, None, head, "changed", true).unwrap();
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let b = Repository::open_bare(p.join("backup").join("repo")).unwrap();
+        assert_eq!(b.find_note(None, head).unwrap().id(), id);
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_meta() {
+        let p = path("git_meta");
+        let r = init_repo(&p.join("src"), &[("a", "a\n")]);
+        r.config()
+            .unwrap()
+            .set_str("devsync.test", "value")
+            .unwrap();
+        let gd = r.path();
+        write(gd, "info/exclude", "*.o\n");
+        write(gd, "hooks/pre-commit", "#!/bin/sh\n");
+        fs::set_permissions(
+            gd.join("hooks/pre-commit"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        write(gd, "hooks/pre-push.sample", "#!/bin/sh\n");
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let t = p.join("backup").join("meta");
+        let cfg = git2::Config::open(&t.join("config")).unwrap();
+        assert_eq!(cfg.get_string("devsync.test").unwrap(), "value");
+        assert_eq!(fs::read_to_string(t.join("info/exclude")).unwrap(), "*.o\n");
+        let m = fs::metadata(t.join("hooks/pre-commit")).unwrap();
+        assert_eq!(m.permissions().mode() & 0o777, 0o755);
+        assert!(!t.join("hooks/pre-push.sample").exists());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_lfs() {
+        let p = path("git_lfs");
+        let oid = |c: char| c.to_string().repeat(64);
+        let pointer = |c: char| {
+            format!(
+                "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize 1\n",
+                oid(c)
+            )
+        };
+        let object = |c: char| {
+            let o = oid(c);
+            format!("{}/{}/{}", &o[0..2], &o[2..4], o)
+        };
+
+        init_repo(&p.join("upstream"), &[("pushed", &pointer('a'))]);
+        let r = clone_repo(&p.join("upstream"), &p.join("src"));
+        commit(&r, &[("local", &pointer('b'))], "local");
+        stage(&r, &[("staged", &pointer('c'))]);
+        for c in ['a', 'b', 'c'] {
+            write(&r.path().join("lfs/objects"), &object(c), "x");
+        }
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let t = p.join("backup").join("lfs");
+        assert!(!t.join(object('a')).exists());
+        assert!(t.join(object('b')).is_file());
+        assert!(t.join(object('c')).is_file());
+
+        // objects no longer referenced are removed
+        write(&t, &object('d'), "x");
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        assert!(!t.join(object('d')).exists());
+        assert!(t.join(object('b')).is_file());
+
+        backup(&p.join("src"), &p.join("backup"), &["--git-ignore-lfs"]);
+        assert!(p.join("backup").join("lfs.ignored").exists());
+        assert!(!t.exists());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_dup_ignored() {
+        let p = path("git_ignored");
+        let r = init_repo(&p.join("src"), &[(".gitignore", "*.log\nbuild/\n")]);
+        let wd = r.workdir().unwrap();
+        for f in [
+            "a.log",
+            "build/keep.env",
+            "build/out/x.o",
+            "build/node_modules/m/y.env",
+            "build/nested/.git/z.env",
+        ] {
+            write(wd, f, "x\n");
+        }
+        write(wd, "small", "s\n");
+        write(wd, "large", &"l".repeat(2048));
+
+        backup(
+            &p.join("src"),
+            &p.join("backup"),
+            &[
+                "--git-include-ignored",
+                "*.log,*.env",
+                "--git-exclude-ignored",
+                "node_modules",
+                "--git-untracked-max-size",
+                "1K",
+            ],
+        );
+        let t = p.join("backup").join("ignored");
+        assert!(t.join("a.log").is_file());
+        assert!(t.join("build/keep.env").is_file());
+        assert!(!t.join("build/out").exists());
+        assert!(!t.join("build/node_modules").exists());
+        assert!(!t.join("build/nested").exists());
+
+        let t = p.join("backup").join("untracked");
+        assert!(t.join("small").is_file());
+        assert!(!t.join("large").exists());
+        let m = utils::manifest_read(&p.join("backup").join("manifest")).unwrap();
+        assert!(m.iter().any(|e| e == &["skipped", "untracked", "large"]));
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        assert!(p.join("backup").join("ignored.ignored").exists());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+}
@@ -0,0 +1,442 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour};
+
+pub struct Hg {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    full: bool,
+    ignore_shelves: bool,
+    ignore_modified: bool,
+    ignore_unknown: bool,
+    ignore_outgoing: bool,
+}
+
+impl Hg {
+    /// Create new flavour with the same options but without [Dir].
+    fn copy(&self) -> Hg {
+        Hg {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            full: self.full,
+            ignore_shelves: self.ignore_shelves,
+            ignore_modified: self.ignore_modified,
+            ignore_unknown: self.ignore_unknown,
+            ignore_outgoing: self.ignore_outgoing,
+        }
+    }
+
+    fn dir_unchecked(&self) -> &Dir {
+        match self.dir.as_ref() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
+    fn subdir_create(&self, n: &str) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let p = &d.target_path.as_path().join(n);
+        utils::create_dir_save(p, true)?;
+        Ok(())
+    }
+
+    fn subdir_rename(&self, n: &str, s: &str) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let p = &d.target_path.as_path().join(n);
+        if p.exists() {
+            fs::remove_dir_all(p)?;
+        }
+        fs::File::create(d.target_path.as_path().join(format!("{}.{}", n, s)))?;
+        Ok(())
+    }
+
+    fn subdir_ignored(&self, n: &str) -> Result<(), SyncError> {
+        self.subdir_rename(n, "ignored")
+    }
+
+    fn subdir_empty(&self, n: &str) -> Result<(), SyncError> {
+        self.subdir_rename(n, "empty")
+    }
+
+    /// Run hg in the repository root with plain output, i. e. user
+    /// configuration that changes the output format is not applied.
+    /// hg must never prompt, there is nobody to answer.
+    fn hg(&self, args: &[&str]) -> Result<Output, SyncError> {
+        let d = self.dir_unchecked();
+        Ok(Command::new("hg")
+            .args(["--noninteractive", "--config", "ui.interactive=false"])
+            .args(args)
+            .current_dir(d.src_path.as_path())
+            .env("HGPLAIN", "1")
+            .stdin(Stdio::null())
+            .output()?)
+    }
+
+    /// Run hg and return its output, fail if hg fails.
+    fn hg_out(&self, args: &[&str]) -> Result<Vec<u8>, SyncError> {
+        let o = self.hg(args)?;
+        if o.status.success() {
+            Ok(o.stdout)
+        } else {
+            Err(SyncError::Failed(format!(
+                "hg {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&o.stderr).trim()
+            )))
+        }
+    }
+
+    /// Record working directory parent, branch and paths in the
+    /// manifest.
+    fn dup_manifest(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let m = d.target_path.as_path().join("manifest");
+
+        let o = self.hg_out(&["log", "-r", ".", "-T", "{node}\\t{branch}"])?;
+        if let Some((node, branch)) = String::from_utf8_lossy(&o).split_once('\t') {
+            utils::manifest_add(&m, &["parent", node])?;
+            utils::manifest_add(&m, &["branch", branch])?;
+        }
+
+        for (n, url) in parse_paths(&self.hg_out(&["paths"])?) {
+            utils::manifest_add(&m, &["path", &n, &url])?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy modified, added and unknown files and record removed and
+    /// missing files in the manifest.
+    fn dup_status(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let m = d.target_path.as_path().join("manifest");
+
+        let mut modified = vec![];
+        let mut unknown = vec![];
+        for (s, p) in parse_status(&self.hg_out(&["status", "--print0", "-marud"])?) {
+            match s {
+                'M' | 'A' => {
+                    if s == 'A' {
+                        utils::manifest_add(&m, &["added", p.to_str().unwrap()])?;
+                    }
+                    if !self.ignore_modified {
+                        modified.push(d.src_path.join(p));
+                    }
+                }
+                'R' => utils::manifest_add(&m, &["removed", p.to_str().unwrap()])?,
+                '!' => utils::manifest_add(&m, &["missing", p.to_str().unwrap()])?,
+                '?' if !self.ignore_unknown => unknown.push(d.src_path.join(p)),
+                _ => (),
+            }
+        }
+
+        for (n, files, ignore) in [
+            ("modified", modified, self.ignore_modified),
+            ("unknown", unknown, self.ignore_unknown),
+        ] {
+            self.subdir_create(n)?;
+            if ignore {
+                self.subdir_ignored(n)?;
+            } else if files.is_empty() {
+                self.subdir_empty(n)?;
+            } else {
+                for f in files.iter().filter(|f| f.symlink_metadata().is_ok()) {
+                    trace!("Backup {} {:?}", n, f);
+                    utils::cp_d(
+                        d.src_path.as_path(),
+                        &d.target_path.as_path().join(n),
+                        f,
+                        true,
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy shelves unless --hg-ignore-shelves is set.
+    fn dup_shelves(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let s = d.src_path.as_path().join(".hg").join("shelved");
+
+        self.subdir_create("shelves")?;
+        if self.ignore_shelves {
+            self.subdir_ignored("shelves")
+        } else if !s.is_dir() || fs::read_dir(&s)?.next().is_none() {
+            self.subdir_empty("shelves")
+        } else {
+            for e in fs::read_dir(&s)?.flatten() {
+                utils::cp(
+                    &s,
+                    &d.target_path.as_path().join("shelves"),
+                    &e.path(),
+                    true,
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Bundle changesets that have not been pushed, i. e. draft and
+    /// secret changesets, unless --hg-ignore-outgoing is set. The
+    /// phases are local, no peer is contacted.
+    fn dup_outgoing(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let b = d.target_path.as_path().join("outgoing.hg");
+
+        if self.ignore_outgoing {
+            fs::File::create(d.target_path.as_path().join("outgoing.ignored"))?;
+            return Ok(());
+        }
+
+        if self
+            .hg_out(&["log", "-r", "not public()", "-T", "{node}\\n"])?
+            .is_empty()
+        {
+            fs::File::create(d.target_path.as_path().join("outgoing.empty"))?;
+            return Ok(());
+        }
+
+        // without public changesets the bundle has no base
+        let base = if self
+            .hg_out(&["log", "-r", "public()", "-l", "1", "-T", "{node}"])?
+            .is_empty()
+        {
+            "null"
+        } else {
+            "public()"
+        };
+
+        let b = b.to_string_lossy();
+        let args = ["bundle", "-r", "not public()", "--base", base, &b];
+
+        // hg returns 1 if there is nothing to bundle
+        let o = self.hg(&args)?;
+        match o.status.code() {
+            Some(0) => Ok(()),
+            Some(1) => {
+                fs::File::create(d.target_path.as_path().join("outgoing.empty"))?;
+                Ok(())
+            }
+            _ => Err(SyncError::Failed(format!(
+                "hg bundle failed: {}",
+                String::from_utf8_lossy(&o.stderr).trim()
+            ))),
+        }
+    }
+
+    /// Run all duplicate steps.
+    fn dup_all(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            utils::rm_dirs_and_files(d.target_path.as_path())?;
+
+            if let Err(e) = self.dup_manifest() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to write manifest because {}", e),
+                });
+            }
+
+            if let Err(e) = self.dup_status() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup status because {}", e),
+                });
+            }
+
+            if let Err(e) = self.dup_shelves() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup shelves because {}", e),
+                });
+            }
+
+            if let Err(e) = self.dup_outgoing() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup outgoing changesets because {}", e),
+                });
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+/// Parse output of 'hg status --print0' into state and path.
+fn parse_status(b: &[u8]) -> Vec<(char, PathBuf)> {
+    String::from_utf8_lossy(b)
+        .split('\0')
+        .filter_map(|e| {
+            let mut c = e.chars();
+            let s = c.next()?;
+            c.next();
+            Some((s, PathBuf::from(c.as_str())))
+        })
+        .collect()
+}
+
+/// Parse output of 'hg paths' into name and url.
+fn parse_paths(b: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(b)
+        .lines()
+        .filter_map(|l| {
+            let (n, url) = l.split_once(" = ")?;
+            Some((n.trim().to_string(), url.trim().to_string()))
+        })
+        .collect()
+}
+
+impl Flavour for Hg {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "hg-ignore", "Ignore Mercurial repositories");
+        opts.optflag(
+            "",
+            "hg-full",
+            "Full backup (default is shelves, outgoing and status)",
+        );
+        opts.optflag("", "hg-ignore-shelves", "Don't backup shelves");
+        opts.optflag(
+            "",
+            "hg-ignore-modified",
+            "Don't backup modified and added files",
+        );
+        opts.optflag("", "hg-ignore-unknown", "Don't backup unknown files");
+        opts.optflag("", "hg-ignore-outgoing", "Don't backup outgoing changesets");
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Hg {
+            dir: Box::new(None),
+            ignore: args.opt_present("hg-ignore"),
+            full: args.opt_present("hg-full"),
+            ignore_shelves: args.opt_present("hg-ignore-shelves"),
+            ignore_modified: args.opt_present("hg-ignore-modified"),
+            ignore_unknown: args.opt_present("hg-ignore-unknown"),
+            ignore_outgoing: args.opt_present("hg-ignore-outgoing"),
+        }
+    }
+
+    /// Probe for '.hg' directory to identify Mercurial repository.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        for d in &d.dirs {
+            if d.file_name().unwrap() == ".hg" {
+                return Some(self.build());
+            }
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy())
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn name(&self) -> &'static str {
+        "Mercurial"
+    }
+
+    fn category(&self) -> Category {
+        Category::Repository
+    }
+
+    /// Recurse if --hg-full is set.
+    fn recurse(&self) -> bool {
+        self.full
+    }
+
+    /// Skip if --hg-ignore is set.
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn stay(&self) -> bool {
+        false
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if !self.full {
+            self.dup_all()
+        } else if let Some(d) = self.dir() {
+            d.dup()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if !self.full {
+            self.dup_all()
+        } else if let Some(d) = self.dir() {
+            d.merge()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let s = parse_status(b"M a.txt\0A sub/b c.txt\0? new\0");
+        assert_eq!(
+            s,
+            vec![
+                ('M', PathBuf::from("a.txt")),
+                ('A', PathBuf::from("sub/b c.txt")),
+                ('?', PathBuf::from("new")),
+            ]
+        );
+        assert!(parse_status(b"").is_empty());
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        let p = parse_paths(b"default = https://hg.example.org/repo\nfork = ssh://x/y\n");
+        assert_eq!(
+            p,
+            vec![
+                (
+                    "default".to_string(),
+                    "https://hg.example.org/repo".to_string()
+                ),
+                ("fork".to_string(), "ssh://x/y".to_string()),
+            ]
+        );
+    }
+}
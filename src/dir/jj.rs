@@ -0,0 +1,280 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::process::Command;
+
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour, Git};
+
+pub struct Jj {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    full: bool,
+    snapshot: bool,
+    /// Template for the colocated Git repository.
+    git: Git,
+}
+
+impl Jj {
+    /// Create new flavour with the same options but without [Dir].
+    fn copy(&self) -> Jj {
+        Jj {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            full: self.full,
+            snapshot: self.snapshot,
+            git: self.git.copy(),
+        }
+    }
+
+    fn dir_unchecked(&self) -> &Dir {
+        match self.dir.as_ref() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
+    /// Run jj log for revisions 'r' with template 't' and split the
+    /// output into records of 'n' fields. The working copy is only
+    /// snapshotted if 'snapshot' is set, otherwise the repository is
+    /// left untouched.
+    fn jj_log(
+        &self,
+        r: &str,
+        t: &str,
+        snapshot: bool,
+        n: usize,
+    ) -> Result<Vec<Vec<String>>, SyncError> {
+        let d = self.dir_unchecked();
+        let mut c = Command::new("jj");
+        c.args(["log", "--no-graph", "--color", "never", "-r", r, "-T", t])
+            .current_dir(d.src_path.as_path());
+        if !snapshot {
+            c.arg("--ignore-working-copy");
+        }
+
+        let o = c.output()?;
+        if o.status.success() {
+            Ok(parse_records(&o.stdout, n))
+        } else {
+            Err(SyncError::Failed(format!(
+                "jj log failed: {}",
+                String::from_utf8_lossy(&o.stderr).trim()
+            )))
+        }
+    }
+
+    /// Record the working copy commit and all changes that are not
+    /// reachable from a remote bookmark in the manifest.
+    fn dup_manifest(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let m = d.target_path.as_path().join("manifest");
+
+        let t = r#"change_id ++ "\t" ++ commit_id ++ "\n""#;
+        for r in self.jj_log("@", t, self.snapshot, 2)? {
+            let r: Vec<&str> = r.iter().map(|f| f.as_str()).collect();
+            utils::manifest_add(&m, &[&["working-copy"], r.as_slice()].concat())?;
+        }
+
+        let t = r#"change_id ++ "\t" ++ commit_id ++ "\t" ++ description.first_line() ++ "\n""#;
+        for r in self.jj_log("remote_bookmarks()..", t, false, 3)? {
+            let r: Vec<&str> = r.iter().map(|f| f.as_str()).collect();
+            utils::manifest_add(&m, &[&["unpushed"], r.as_slice()].concat())?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy the '.jj' store with operation log and working copy
+    /// state to 'jj', only files that changed since the last backup
+    /// are copied.
+    fn dup_store(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        trace!("Backup jj store of {:?}", d.src_path);
+        utils::sync_tree(
+            &d.src_path.as_path().join(".jj"),
+            &d.target_path.as_path().join("jj"),
+            true,
+        )
+    }
+
+    /// Backup a colocated Git repository to 'git' like the [Git]
+    /// flavour does.
+    fn dup_git(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        if !d.src_path.as_path().join(".git").exists() || self.git.skip() {
+            let t = d.target_path.as_path().join("git");
+            if t.exists() {
+                fs::remove_dir_all(t)?;
+            }
+            return Ok(());
+        }
+
+        self.git
+            .dup_nested(d.sub_dir(d.src_path.clone(), d.target_path.as_path().join("git")))
+    }
+
+    /// Run all duplicate steps.
+    fn dup_all(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            // the store and the Git backup are updated incrementally
+            utils::rm_dirs_and_files_except(d.target_path.as_path(), &["jj", "git"])?;
+
+            if let Err(e) = self.dup_manifest() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to write manifest because {}", e),
+                });
+            }
+
+            if let Err(e) = self.dup_store() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup store because {}", e),
+                });
+            }
+
+            if let Err(e) = self.dup_git() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup Git repository because {}", e),
+                });
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+/// Split records of 'n' tab separated fields, one per line. The last
+/// field keeps its tabs.
+fn parse_records(b: &[u8], n: usize) -> Vec<Vec<String>> {
+    String::from_utf8_lossy(b)
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.splitn(n, '\t').map(|f| f.to_string()).collect())
+        .collect()
+}
+
+impl Flavour for Jj {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "jj-ignore", "Ignore Jujutsu repositories");
+        opts.optflag(
+            "",
+            "jj-full",
+            "Full backup (default is store, unpushed changes and Git)",
+        );
+        opts.optflag(
+            "",
+            "jj-no-snapshot",
+            "Don't snapshot the working copy before backup",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Jj {
+            dir: Box::new(None),
+            ignore: args.opt_present("jj-ignore"),
+            full: args.opt_present("jj-full"),
+            snapshot: !args.opt_present("jj-no-snapshot"),
+            git: Git::template(args),
+        }
+    }
+
+    /// Probe for '.jj' directory to identify Jujutsu repository.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        for d in &d.dirs {
+            if d.file_name().unwrap() == ".jj" {
+                return Some(self.build());
+            }
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy())
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn name(&self) -> &'static str {
+        "Jujutsu"
+    }
+
+    fn category(&self) -> Category {
+        Category::Repository
+    }
+
+    /// Recurse if --jj-full is set.
+    fn recurse(&self) -> bool {
+        self.full
+    }
+
+    /// Skip if --jj-ignore is set.
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn stay(&self) -> bool {
+        false
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if !self.full {
+            self.dup_all()
+        } else if let Some(d) = self.dir() {
+            d.dup()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if !self.full {
+            self.dup_all()
+        } else if let Some(d) = self.dir() {
+            d.merge()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let r = parse_records(b"kpqx\t1a2b\tFix parser\nzzzz\t0000\t\n\n", 3);
+        assert_eq!(r.len(), 2);
+        assert_eq!(r[0], vec!["kpqx", "1a2b", "Fix parser"]);
+        assert_eq!(r[1], vec!["zzzz", "0000", ""]);
+        let r = parse_records(b"kpqx\t1a2b\tFix\tparser\n", 3);
+        assert_eq!(r[0], vec!["kpqx", "1a2b", "Fix\tparser"]);
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::{Category, Dir, Flavour};
+
+/// Gradle build scripts.
+const GRADLE_FILES: [&str; 4] = [
+    "build.gradle",
+    "build.gradle.kts",
+    "settings.gradle",
+    "settings.gradle.kts",
+];
+
+pub struct Jvm {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+}
+
+impl Flavour for Jvm {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "jvm-sync", "Sync Gradle and Maven build directories");
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Jvm {
+            dir: Box::new(None),
+            ignore: !args.opt_present("jvm-sync"),
+        }
+    }
+
+    /// Look for directories 'build' or '.gradle' next to a Gradle
+    /// build script or 'target' next to 'pom.xml'.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        let n = d.src_path.file_name()?;
+        let p = d.src_path.parent()?;
+        if ((n == "build" || n == ".gradle") && GRADLE_FILES.iter().any(|f| p.join(f).is_file()))
+            || (n == "target" && p.join("pom.xml").is_file())
+        {
+            Some(self.build())
+        } else {
+            None
+        }
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(Jvm {
+            dir: Box::new(None),
+            ignore: self.ignore,
+        })
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Build
+    }
+
+    /// Recurse if --jvm-sync is set.
+    fn recurse(&self) -> bool {
+        !self.skip()
+    }
+
+    /// Skip if --jvm-sync is not set.
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn name(&self) -> &'static str {
+        "JVM"
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bitflags::bitflags;
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour, Git};
+
+/// Files generated by Kbuild, matched against the file name or
+/// against the path if the pattern contains '/'.
+const ARTEFACTS: [&str; 32] = [
+    "*.o",
+    "*.ko",
+    "*.mod",
+    "*.mod.c",
+    "built-in.a",
+    "lib.a",
+    "*.order",
+    "*.symvers",
+    "*.cmd",
+    ".*.d",
+    "*.dtb",
+    "*.dtbo",
+    "*.lst",
+    "vdso*.so",
+    "vdso*.so.dbg",
+    "*.tmp",
+    ".tmp_*",
+    "vmlinux",
+    "vmlinux.o",
+    "vmlinux.a",
+    "vmlinux.symvers",
+    "vmlinux.unstripped",
+    "System.map",
+    "modules.builtin*",
+    "**/arch/*/boot/bzImage",
+    "**/arch/*/boot/zImage",
+    "**/arch/*/boot/Image",
+    "**/arch/*/boot/Image.gz",
+    "**/arch/*/kernel/vmlinux.lds",
+    "**/scripts/basic/fixdep",
+    "**/scripts/mod/modpost",
+    "**/scripts/kconfig/conf",
+];
+
+/// Directories generated by Kbuild.
+const ARTEFACT_DIRS: [&str; 3] = [".tmp_versions", "include/generated", "include/config"];
+
+/// What the directory is.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    /// Top level of the source tree, 'true' if backed up as Git
+    /// repository without --git-full.
+    Source(bool),
+    /// Build directory of an out-of-tree build with 'O='.
+    Build,
+    /// Any directory in the source tree.
+    Plain,
+}
+
+pub struct Kernel {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    build_sync: bool,
+    kind: Kind,
+    /// Build directories in the source tree that are excluded.
+    builds: Vec<PathBuf>,
+    /// Template for source trees that are Git repositories.
+    git: Git,
+}
+
+bitflags! {
+    #[derive(PartialEq)]
+    struct RequiredFiles: u8 {
+        const NONE = 0;
+        const KBUILD = 1;
+        const KCONFIG = 2;
+        const MAINTAINERS = 4;
+        const ARCH = 8;
+        const ALL = Self::KBUILD.bits() | Self::KCONFIG.bits() | Self::MAINTAINERS.bits() | Self::ARCH.bits();
+    }
+}
+
+impl Kernel {
+    fn copy(&self, kind: Kind) -> Kernel {
+        Kernel {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            build_sync: self.build_sync,
+            kind,
+            builds: vec![],
+            git: self.git.copy(),
+        }
+    }
+
+    fn dir_unchecked(&self) -> &Dir {
+        match self.dir.as_ref() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
+    /// If only the configuration of the directory is saved.
+    fn config_only(&self) -> bool {
+        self.kind == Kind::Build && !self.build_sync
+    }
+
+    /// Save '.config' of the excluded build directories to 't', with
+    /// 'top' set also the one of the source tree.
+    fn dup_configs(&self, t: &Path, top: bool) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let mut configs = vec![];
+        if top {
+            configs.push(PathBuf::from(".config"));
+        }
+        for e in &self.builds {
+            configs.push(e.strip_prefix(&d.src_path).unwrap().join(".config"));
+        }
+        for c in configs {
+            if d.src_path.join(&c).is_file() {
+                trace!("Backup kernel configuration {:?}", c);
+                utils::cp_r_d(&d.src_path, t, &c, true)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Save '.config' of a build directory and record the source
+    /// tree it belongs to.
+    fn dup_build_config(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        utils::cp_r(&d.src_path, &d.target_path, Path::new(".config"), true)?;
+        let s = fs::read_link(d.src_path.join("source"))?;
+        utils::manifest_add(
+            &d.target_path.join("manifest"),
+            &["source", &s.to_string_lossy()],
+        )
+    }
+
+    /// Run the duplicate steps for the kind of directory, configs
+    /// of source trees backed up as Git repository go to 'kernel'.
+    fn dup_kind(&self, merge: bool) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let git = self.kind == Kind::Source(true);
+        let r = if git {
+            self.git
+                .dup_nested(d.sub_dir(d.src_path.clone(), d.target_path.clone()))
+        } else if self.config_only() {
+            return self.dup_build_config();
+        } else if merge {
+            d.merge()
+        } else {
+            d.dup()
+        };
+
+        let t = d
+            .target_path
+            .as_path()
+            .join(if git { "kernel" } else { "" });
+        if let Err(e) = self.dup_configs(&t, git) {
+            d.send_runtime(stats::Info {
+                category: self.category(),
+                name: self.name().to_string(),
+                desc: format!("Failed to backup configuration because {}", e),
+            });
+        }
+        r
+    }
+}
+
+/// If 'p' is the build directory of an out-of-tree build, Kbuild
+/// creates the link 'source' to the source tree in it.
+fn is_build_dir(p: &Path) -> bool {
+    p.join(".config").is_file()
+        && fs::symlink_metadata(p.join("source")).is_ok_and(|m| m.file_type().is_symlink())
+        && p.join("source").join("Kbuild").is_file()
+}
+
+/// If 'p' is generated by Kbuild.
+fn is_artefact(p: &Path, dir: bool) -> bool {
+    if dir {
+        ARTEFACT_DIRS.iter().any(|a| p.ends_with(a))
+    } else {
+        let n = Path::new(p.file_name().unwrap());
+        ARTEFACTS
+            .iter()
+            .any(|a| utils::glob_match(a, if a.contains('/') { p } else { n }))
+    }
+}
+
+impl Flavour for Kernel {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "kernel-ignore", "Ignore Linux kernel directories");
+        opts.optflag(
+            "",
+            "kernel-build-sync",
+            "Sync Linux kernel build products and build directories",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Kernel {
+            dir: Box::new(None),
+            ignore: args.opt_present("kernel-ignore"),
+            build_sync: args.opt_present("kernel-build-sync"),
+            kind: Kind::Plain,
+            builds: vec![],
+            git: Git::template(args),
+        }
+    }
+
+    /// Look for files 'Kbuild', 'Kconfig' and 'MAINTAINERS' and
+    /// directory 'arch' to identify the kernel source tree or for a
+    /// build directory that links to it.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        if is_build_dir(&d.src_path) {
+            return Some(Box::new(self.copy(Kind::Build)));
+        }
+
+        let mut m = RequiredFiles::NONE;
+        for f in &d.files {
+            let f = f.file_name().unwrap();
+            if f == "Kbuild" {
+                m |= RequiredFiles::KBUILD;
+            } else if f == "Kconfig" {
+                m |= RequiredFiles::KCONFIG;
+            } else if f == "MAINTAINERS" {
+                m |= RequiredFiles::MAINTAINERS;
+            }
+        }
+        if d.dirs.iter().any(|d| d.file_name().unwrap() == "arch") {
+            m |= RequiredFiles::ARCH;
+        }
+        if m == RequiredFiles::ALL {
+            return Some(Box::new(self.copy(Kind::Source(false))));
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy(Kind::Plain))
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Special
+    }
+
+    /// Source trees that are Git repositories and build directories
+    /// are not scanned.
+    fn recurse(&self) -> bool {
+        match self.kind {
+            Kind::Source(git) => !git,
+            _ => !self.config_only(),
+        }
+    }
+
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn name(&self) -> &'static str {
+        "Kernel"
+    }
+
+    /// Find out if the source tree is backed up as Git repository and
+    /// filter the Kbuild artefacts unless --kernel-build-sync is set,
+    /// with --git-full the tree is copied without them, with
+    /// --git-ignore '.git' is excluded.
+    fn prepare(&mut self) -> Result<(), SyncError> {
+        let (build_sync, skip_git, config_only) =
+            (self.build_sync, self.git.skip(), self.config_only());
+        let git = !skip_git && !self.git.recurse();
+        let d = match self.dir.as_mut() {
+            Some(d) => d,
+            None => {
+                return Err(SyncError::Failed(
+                    "Cannot prepare synchronization without directory".to_string(),
+                ))
+            }
+        };
+
+        if let Kind::Source(_) = self.kind {
+            if skip_git {
+                d.dirs.retain(|e| e.file_name().unwrap() != ".git");
+            }
+            self.kind = Kind::Source(git && d.src_path.join(".git").exists());
+        }
+        if !build_sync {
+            let (builds, dirs) = d.dirs.drain(..).partition(|e| is_build_dir(e));
+            self.builds = builds;
+            d.dirs = dirs;
+            d.dirs.retain(|e| !is_artefact(e, true));
+            d.files.retain(|f| !is_artefact(f, false));
+        }
+
+        d.ensure_target_path()?;
+        if config_only {
+            utils::rm_dirs_and_files(&d.target_path)?;
+        }
+        Ok(())
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        self.dup_kind(false)
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        self.dup_kind(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_artefact() {
+        assert!(is_artefact(Path::new("drivers/net/foo.o"), false));
+        assert!(is_artefact(Path::new("drivers/net/.foo.o.cmd"), false));
+        assert!(is_artefact(Path::new("vmlinux.a"), false));
+        assert!(is_artefact(Path::new("include/generated"), true));
+        assert!(!is_artefact(Path::new("drivers/net/foo.c"), false));
+        assert!(!is_artefact(Path::new(".config"), false));
+        assert!(!is_artefact(Path::new("include/linux"), true));
+        assert!(!is_artefact(
+            Path::new("arch/x86/kernel/vmlinux.lds.S"),
+            false
+        ));
+        assert!(!is_artefact(
+            Path::new("include/asm-generic/vmlinux.lds.h"),
+            false
+        ));
+        assert!(is_artefact(Path::new("arch/arm64/boot/Image"), false));
+        assert!(!is_artefact(Path::new("Documentation/Image"), false));
+        assert!(is_artefact(Path::new("drivers/net/.foo.o.d"), false));
+        assert!(!is_artefact(Path::new("tools/lib/foo.d"), false));
+        assert!(!is_artefact(Path::new("tools/lib/libfoo.so"), false));
+        assert!(is_artefact(Path::new("scripts/basic/fixdep"), false));
+        assert!(is_artefact(Path::new("scripts/mod/modpost"), false));
+        assert!(is_artefact(Path::new("scripts/kconfig/conf"), false));
+        assert!(!is_artefact(Path::new("scripts/kconfig/conf.c"), false));
+        assert!(!is_artefact(Path::new("tools/perf/modpost"), false));
+        assert!(is_artefact(Path::new("arch/x86/kernel/vmlinux.lds"), false));
+        assert!(!is_artefact(Path::new("arch/x86/vmlinux.lds"), false));
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+use super::{utils, Category, Dir, Flavour};
+
+/// How a cache directory is recognised.
+enum Marker {
+    /// File next to the cache directory, may be a glob.
+    Parent(&'static str),
+    /// File inside the cache directory.
+    Inside(&'static str),
+}
+
+/// Cache directory of a language toolchain.
+struct Cache {
+    /// Language as used for the option '--<lang>-sync'.
+    lang: &'static str,
+    /// Name of the cache directory.
+    dir: &'static str,
+    /// One of the markers must exist.
+    markers: &'static [Marker],
+}
+
+/// Languages with their display name.
+const LANGS: [(&str, &str); 4] = [
+    ("go", "Go"),
+    ("zig", "Zig"),
+    ("haskell", "Haskell"),
+    ("ocaml", "OCaml"),
+];
+
+const CACHES: [Cache; 8] = [
+    Cache {
+        lang: "go",
+        dir: "go-build",
+        markers: &[Marker::Inside("trim.txt")],
+    },
+    Cache {
+        lang: "go",
+        dir: "mod",
+        markers: &[Marker::Inside("cache/download")],
+    },
+    Cache {
+        lang: "zig",
+        dir: "zig-cache",
+        markers: &[Marker::Parent("build.zig")],
+    },
+    Cache {
+        lang: "zig",
+        dir: ".zig-cache",
+        markers: &[Marker::Parent("build.zig")],
+    },
+    Cache {
+        lang: "haskell",
+        dir: ".stack-work",
+        markers: &[
+            Marker::Parent("stack.yaml"),
+            Marker::Parent("package.yaml"),
+            Marker::Parent("*.cabal"),
+        ],
+    },
+    Cache {
+        lang: "haskell",
+        dir: "dist-newstyle",
+        markers: &[Marker::Parent("cabal.project"), Marker::Parent("*.cabal")],
+    },
+    Cache {
+        lang: "ocaml",
+        dir: "_build",
+        markers: &[
+            Marker::Parent("dune-project"),
+            Marker::Parent("dune-workspace"),
+        ],
+    },
+    Cache {
+        lang: "ocaml",
+        dir: "_opam",
+        markers: &[Marker::Inside(".opam-switch")],
+    },
+];
+
+impl Cache {
+    /// If 'p' is a cache directory of this kind.
+    fn matches(&self, p: &Path) -> bool {
+        if p.file_name() != Some(OsStr::new(self.dir)) {
+            return false;
+        }
+        self.markers.iter().any(|m| match m {
+            Marker::Inside(f) => p.join(f).exists(),
+            Marker::Parent(g) => {
+                p.parent()
+                    .and_then(|pp| pp.read_dir().ok())
+                    .is_some_and(|mut es| {
+                        es.any(|e| e.is_ok_and(|e| utils::glob_match(g, Path::new(&e.file_name()))))
+                    })
+            }
+        })
+    }
+}
+
+pub struct LangCache {
+    dir: Box<Option<Dir>>,
+    /// Languages with --<lang>-sync set.
+    sync: Vec<&'static str>,
+    ignore: bool,
+}
+
+impl Flavour for LangCache {
+    fn init_opts(opts: &mut getopts::Options) {
+        for (l, n) in LANGS {
+            opts.optflag(
+                "",
+                &format!("{}-sync", l),
+                &format!("Sync {} build caches", n),
+            );
+        }
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        LangCache {
+            dir: Box::new(None),
+            sync: LANGS
+                .iter()
+                .filter(|(l, _)| args.opt_present(&format!("{}-sync", l)))
+                .map(|(l, _)| *l)
+                .collect(),
+            ignore: false,
+        }
+    }
+
+    /// Look up the directory in the table of cache directories and
+    /// check for one of its markers.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        let c = CACHES.iter().find(|c| c.matches(&d.src_path))?;
+        Some(Box::new(LangCache {
+            dir: Box::new(None),
+            sync: self.sync.clone(),
+            ignore: !self.sync.contains(&c.lang),
+        }))
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(LangCache {
+            dir: Box::new(None),
+            sync: self.sync.clone(),
+            ignore: self.ignore,
+        })
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Build
+    }
+
+    /// Recurse if --<lang>-sync is set.
+    fn recurse(&self) -> bool {
+        !self.skip()
+    }
+
+    /// Skip if --<lang>-sync is not set.
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn name(&self) -> &'static str {
+        "LangCache"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_caches() {
+        for c in &CACHES {
+            assert!(LANGS.iter().any(|(l, _)| *l == c.lang));
+            assert!(!c.markers.is_empty());
+        }
+        for (l, _) in LANGS {
+            assert!(CACHES.iter().any(|c| c.lang == l));
+        }
+    }
+}
@@ -62,7 +62,7 @@ impl Flavour for Meson {
     }
 
     fn set_dir(&mut self, d: Dir) {
-        self.dir = Box::new(Some(d));
+        *self.dir = Some(d);
     }
 
     fn dir(&self) -> &Option<Dir> {
@@ -20,8 +20,22 @@ pub mod yocto;
 pub use self::yocto::Yocto;
 pub mod sysroot;
 pub use self::sysroot::Sysroot;
+pub mod buildroot;
+pub use self::buildroot::Buildroot;
+pub mod openwrt;
+pub use self::openwrt::OpenWrt;
+pub mod kernel;
+pub use self::kernel::Kernel;
+pub mod repo;
+pub use self::repo::Repo;
+pub mod west;
+pub use self::west::West;
 
 // build directories
+pub mod autotools;
+pub use self::autotools::Autotools;
+pub mod bazel;
+pub use self::bazel::Bazel;
 pub mod cmake;
 pub use self::cmake::Cmake;
 pub mod flutter;
@@ -30,14 +44,26 @@ pub mod meson;
 pub use self::meson::Meson;
 pub mod ninja;
 pub use self::ninja::Ninja;
+pub mod jvm;
+pub use self::jvm::Jvm;
+pub mod langcache;
+pub use self::langcache::LangCache;
+pub mod node;
+pub use self::node::Node;
+pub mod python;
+pub use self::python::Python;
 pub mod cargo;
 pub use self::cargo::Cargo;
 
 // repositories
+pub mod jj;
+pub use self::jj::Jj;
 pub mod git;
 pub use self::git::Git;
 pub mod svn;
 pub use self::svn::Svn;
+pub mod hg;
+pub use self::hg::Hg;
 
 // plain directories
 pub mod simple;
@@ -97,6 +123,14 @@ impl Dir {
         self
     }
 
+    /// Create directory for a nested flavour, job, configuration and
+    /// statistics channel are shared with this directory.
+    pub fn sub_dir(&self, src: PathBuf, target: PathBuf) -> Dir {
+        Dir::new(self.job, self.config.clone(), self.stats_chn.clone())
+            .set_src_path(src)
+            .set_target_path(target)
+    }
+
     /// Helper function for [Flavour::prepare] default
     /// implementation.
     pub fn ensure_target_path(&mut self) -> Result<(), SyncError> {
@@ -583,4 +617,508 @@ mod test {
         let _ = fs::remove_dir_all(d.src_path);
         let _ = fs::remove_dir_all(d.target_path);
     }
+
+    /// Build the template of flavour 'F' for the probe table.
+    fn template<F: Flavour + Send + Sync + 'static>(
+        args: &getopts::Matches,
+    ) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(F::template(args))
+    }
+
+    /// Directory layout for a probe. Entries ending with '/' are
+    /// directories, 'a->b' are symlinks, 'a=b' are files with content
+    /// and all others are empty files.
+    struct Probe {
+        entries: &'static [&'static str],
+        /// Probed directory relative to the layout.
+        dir: &'static str,
+        /// Command line arguments for the template.
+        args: &'static [&'static str],
+        flavour: fn(&getopts::Matches) -> Box<dyn Flavour + Send + Sync>,
+        /// 'None' if the directory is not claimed, otherwise if it
+        /// is skipped.
+        claim: Option<bool>,
+        /// Subdirectories left after [Flavour::prepare], if checked.
+        dirs: Option<&'static [&'static str]>,
+    }
+
+    const PROBES: &[Probe] = &[
+        Probe {
+            entries: &[
+                ".west/config=[manifest]\npath = zephyr\n",
+                "zephyr/",
+                "build/",
+            ],
+            dir: "",
+            args: &[],
+            flavour: template::<West>,
+            claim: Some(false),
+            dirs: Some(&["build", "zephyr"]),
+        },
+        Probe {
+            entries: &[".west/", "build/"],
+            dir: "",
+            args: &[],
+            flavour: template::<West>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &[
+                "module/.git/HEAD=ref: refs/heads/main\n",
+                "module/.git/objects/",
+                "module/.git/refs/heads/manifest-rev=0123456789abcdef0123456789abcdef01234567\n",
+            ],
+            dir: "module",
+            args: &[],
+            flavour: template::<West>,
+            claim: Some(false),
+            dirs: None,
+        },
+        Probe {
+            entries: &[
+                "module/.git/HEAD=ref: refs/heads/main\n",
+                "module/.git/objects/",
+                "module/.git/refs/heads/manifest-rev=0123456789abcdef0123456789abcdef01234567\n",
+            ],
+            dir: "module",
+            args: &["--git-full"],
+            flavour: template::<West>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &[
+                "other/.git/HEAD=ref: refs/heads/main\n",
+                "other/.git/objects/",
+                "other/.git/refs/",
+            ],
+            dir: "other",
+            args: &[],
+            flavour: template::<West>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &["package.json", "package-lock.json", "node_modules/"],
+            dir: "node_modules",
+            args: &[],
+            flavour: template::<Node>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &["package.json", "node_modules/"],
+            dir: "node_modules",
+            args: &[],
+            flavour: template::<Node>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &["package-lock.json", "lib/node_modules/"],
+            dir: "lib/node_modules",
+            args: &[],
+            flavour: template::<Node>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &["package.json", "node_modules/"],
+            dir: "node_modules",
+            args: &["--node-sync"],
+            flavour: template::<Node>,
+            claim: Some(false),
+            dirs: None,
+        },
+        Probe {
+            entries: &["settings.gradle.kts", "build/"],
+            dir: "build",
+            args: &[],
+            flavour: template::<Jvm>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &["build.gradle", ".gradle/"],
+            dir: ".gradle",
+            args: &["--jvm-sync"],
+            flavour: template::<Jvm>,
+            claim: Some(false),
+            dirs: None,
+        },
+        Probe {
+            entries: &["pom.xml", "target/"],
+            dir: "target",
+            args: &[],
+            flavour: template::<Jvm>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &["pom.xml", "build/"],
+            dir: "build",
+            args: &[],
+            flavour: template::<Jvm>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &["build/config.status", "build/config.log"],
+            dir: "build",
+            args: &[],
+            flavour: template::<Autotools>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &["build/config.status"],
+            dir: "build",
+            args: &["--autotools-config"],
+            flavour: template::<Autotools>,
+            claim: Some(false),
+            dirs: None,
+        },
+        Probe {
+            entries: &["configure", "config.status"],
+            dir: "",
+            args: &[],
+            flavour: template::<Autotools>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &["WORKSPACE", "src/", "bazel-bin/", "bazel-out/"],
+            dir: "",
+            args: &[],
+            flavour: template::<Bazel>,
+            claim: Some(false),
+            dirs: Some(&["src"]),
+        },
+        Probe {
+            entries: &["MODULE.bazel", "src/", "bazel-bin/"],
+            dir: "",
+            args: &["--bazel-sync"],
+            flavour: template::<Bazel>,
+            claim: Some(false),
+            dirs: Some(&["bazel-bin", "src"]),
+        },
+        Probe {
+            entries: &["WORKSPACE", "src/", ".git/"],
+            dir: "",
+            args: &["--git-ignore"],
+            flavour: template::<Bazel>,
+            claim: Some(false),
+            dirs: Some(&["src"]),
+        },
+        Probe {
+            entries: &["pants.toml", "dist/", ".pants.d/"],
+            dir: "",
+            args: &[],
+            flavour: template::<Bazel>,
+            claim: Some(false),
+            dirs: Some(&[]),
+        },
+        Probe {
+            entries: &["DO_NOT_BUILD_HERE", "execroot/"],
+            dir: "",
+            args: &[],
+            flavour: template::<Bazel>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &["BUILD", "src/"],
+            dir: "",
+            args: &[],
+            flavour: template::<Bazel>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &["dune-project", "_build/"],
+            dir: "_build",
+            args: &[],
+            flavour: template::<LangCache>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &["_build/"],
+            dir: "_build",
+            args: &[],
+            flavour: template::<LangCache>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &["go-build/trim.txt"],
+            dir: "go-build",
+            args: &["--go-sync"],
+            flavour: template::<LangCache>,
+            claim: Some(false),
+            dirs: None,
+        },
+        Probe {
+            entries: &["go-build/"],
+            dir: "go-build",
+            args: &["--go-sync"],
+            flavour: template::<LangCache>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &["app.cabal", "dist-newstyle/"],
+            dir: "dist-newstyle",
+            args: &[],
+            flavour: template::<LangCache>,
+            claim: Some(true),
+            dirs: None,
+        },
+        Probe {
+            entries: &[
+                "Config.in",
+                "package/",
+                "board/",
+                "configs/",
+                "dl/",
+                "output/",
+            ],
+            dir: "",
+            args: &[],
+            flavour: template::<Buildroot>,
+            claim: Some(false),
+            dirs: Some(&["board", "configs", "package"]),
+        },
+        Probe {
+            entries: &[
+                "Config.in",
+                "package/",
+                "board/",
+                "configs/",
+                "dl/",
+                "output/",
+            ],
+            dir: "",
+            args: &["--buildroot-dl-sync", "--buildroot-output-sync"],
+            flavour: template::<Buildroot>,
+            claim: Some(false),
+            dirs: Some(&["board", "configs", "dl", "output", "package"]),
+        },
+        Probe {
+            entries: &["package/", "board/", "configs/"],
+            dir: "",
+            args: &[],
+            flavour: template::<Buildroot>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &[
+                "feeds.conf.default",
+                "package/",
+                "target/",
+                "dl/",
+                "build_dir/",
+                "staging_dir/",
+                "bin/",
+                "feeds/",
+            ],
+            dir: "",
+            args: &[],
+            flavour: template::<OpenWrt>,
+            claim: Some(false),
+            dirs: Some(&["feeds", "package", "target"]),
+        },
+        Probe {
+            entries: &[
+                "feeds.conf.default",
+                "package/",
+                "target/",
+                "build_dir/",
+                "bin/",
+            ],
+            dir: "",
+            args: &["--openwrt-build-sync"],
+            flavour: template::<OpenWrt>,
+            claim: Some(false),
+            dirs: Some(&["bin", "build_dir", "package", "target"]),
+        },
+        Probe {
+            entries: &["feeds.conf.default", "package/"],
+            dir: "",
+            args: &[],
+            flavour: template::<OpenWrt>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &[
+                "Kbuild",
+                "Kconfig",
+                "MAINTAINERS",
+                "arch/",
+                "drivers/",
+                ".tmp_versions/",
+                "build/.config",
+                "build/source->..",
+            ],
+            dir: "",
+            args: &[],
+            flavour: template::<Kernel>,
+            claim: Some(false),
+            dirs: Some(&["arch", "drivers"]),
+        },
+        Probe {
+            entries: &[
+                "Kbuild",
+                "Kconfig",
+                "MAINTAINERS",
+                "arch/",
+                "build/.config",
+                "build/source->..",
+            ],
+            dir: "build",
+            args: &[],
+            flavour: template::<Kernel>,
+            claim: Some(false),
+            dirs: Some(&[]),
+        },
+        Probe {
+            entries: &["Kbuild", "Kconfig", "MAINTAINERS", "arch/", ".git/"],
+            dir: "",
+            args: &["--git-ignore"],
+            flavour: template::<Kernel>,
+            claim: Some(false),
+            dirs: Some(&["arch"]),
+        },
+        Probe {
+            entries: &["Kbuild", "Kconfig", "arch/"],
+            dir: "",
+            args: &[],
+            flavour: template::<Kernel>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &[".repo/manifests/", "app/", "plain/"],
+            dir: "",
+            args: &[],
+            flavour: template::<Repo>,
+            claim: Some(false),
+            dirs: Some(&["app", "plain"]),
+        },
+        Probe {
+            entries: &[
+                ".repo/projects/app.git/HEAD=ref: refs/heads/main\n",
+                ".repo/projects/app.git/objects/",
+                ".repo/projects/app.git/refs/",
+                "app/.git->../.repo/projects/app.git",
+            ],
+            dir: "app",
+            args: &[],
+            flavour: template::<Repo>,
+            claim: Some(false),
+            dirs: None,
+        },
+        Probe {
+            entries: &[
+                ".repo/projects/app.git/HEAD=ref: refs/heads/main\n",
+                ".repo/projects/app.git/objects/",
+                ".repo/projects/app.git/refs/",
+                "app/.git->../.repo/projects/app.git",
+            ],
+            dir: "app",
+            args: &["--git-full"],
+            flavour: template::<Repo>,
+            claim: None,
+            dirs: None,
+        },
+        Probe {
+            entries: &[".repo/manifests/", "plain/"],
+            dir: "plain",
+            args: &[],
+            flavour: template::<Repo>,
+            claim: None,
+            dirs: None,
+        },
+    ];
+
+    fn create_entry(p: &Path, e: &str) {
+        let (n, content, link) = match (e.split_once("->"), e.split_once('=')) {
+            (Some((n, l)), _) => (n, None, Some(l)),
+            (_, Some((n, c))) => (n, Some(c), None),
+            _ => (e, None, None),
+        };
+        let f = p.join(n);
+        if n.ends_with('/') {
+            fs::create_dir_all(&f).expect("Failed to create path");
+            return;
+        }
+        fs::create_dir_all(f.parent().unwrap()).expect("Failed to create path");
+        if let Some(l) = link {
+            std::os::unix::fs::symlink(l, &f).expect("Failed to create symlink");
+        } else {
+            fs::write(&f, content.unwrap_or_default()).expect("Failed to create file");
+        }
+    }
+
+    #[test]
+    fn test_probe() {
+        let p = path().join("probe");
+        let (cfg, stats) = init(false, false);
+        let mut opts = getopts::Options::new();
+        Git::init_opts(&mut opts);
+        West::init_opts(&mut opts);
+        Node::init_opts(&mut opts);
+        Jvm::init_opts(&mut opts);
+        Autotools::init_opts(&mut opts);
+        Bazel::init_opts(&mut opts);
+        LangCache::init_opts(&mut opts);
+        Buildroot::init_opts(&mut opts);
+        OpenWrt::init_opts(&mut opts);
+        Kernel::init_opts(&mut opts);
+        Repo::init_opts(&mut opts);
+
+        for (i, c) in PROBES.iter().enumerate() {
+            let sp = p.join(i.to_string());
+            utils::create_dir_save(&sp, true).expect("Failed to create path");
+            for e in c.entries {
+                create_entry(&sp, e);
+            }
+            let dir = || {
+                let mut d = Dir::new(0, cfg.clone(), stats.sender().clone())
+                    .set_src_path(sp.join(c.dir))
+                    .set_target_path(p.join(format!("{}.target", i)));
+                let _ = utils::save_dirs_and_files(
+                    &sp.join(c.dir),
+                    &mut d.dirs,
+                    &mut d.files,
+                    None,
+                    false,
+                );
+                d
+            };
+
+            let f = (c.flavour)(&opts.parse(c.args).unwrap()).probe(&dir());
+            assert_eq!(f.as_ref().map(|f| f.skip()), c.claim, "probe {}", i);
+            if let (Some(mut f), Some(dirs)) = (f, c.dirs) {
+                f.set_dir(dir());
+                f.prepare().expect("Failed to prepare synchronization");
+                let mut l: Vec<_> = f
+                    .dir()
+                    .as_ref()
+                    .unwrap()
+                    .dirs
+                    .iter()
+                    .map(|e| e.file_name().unwrap().to_string_lossy().to_string())
+                    .collect();
+                l.sort();
+                assert_eq!(l, dirs, "probe {}", i);
+            }
+        }
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
 }
@@ -0,0 +1,71 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::{Category, Dir, Flavour};
+
+pub struct Node {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+}
+
+impl Flavour for Node {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "node-sync", "Sync Node.js node_modules directories");
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Node {
+            dir: Box::new(None),
+            ignore: !args.opt_present("node-sync"),
+        }
+    }
+
+    /// Look for directory 'node_modules' next to 'package.json'.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        if d.src_path.file_name()? == "node_modules"
+            && d.src_path.parent()?.join("package.json").is_file()
+        {
+            Some(self.build())
+        } else {
+            None
+        }
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(Node {
+            dir: Box::new(None),
+            ignore: self.ignore,
+        })
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Build
+    }
+
+    /// Recurse if --node-sync is set.
+    fn recurse(&self) -> bool {
+        !self.skip()
+    }
+
+    /// Skip if --node-sync is not set.
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn name(&self) -> &'static str {
+        "Node"
+    }
+}
@@ -0,0 +1,197 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+use bitflags::bitflags;
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour};
+
+/// Build output directories that are excluded.
+const BUILD_DIRS: [&str; 4] = ["build_dir", "staging_dir", "bin", "tmp"];
+
+pub struct OpenWrt {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    ignore_downloads: bool,
+    ignore_build: bool,
+    /// Top level directory of the OpenWrt tree.
+    root: bool,
+}
+
+bitflags! {
+    #[derive(PartialEq)]
+    struct RequiredFiles: u8 {
+        const NONE = 0;
+        const FEEDS = 1;
+        const PACKAGE = 2;
+        const TARGET = 4;
+        const ALL = Self::FEEDS.bits() | Self::PACKAGE.bits() | Self::TARGET.bits();
+    }
+}
+
+impl OpenWrt {
+    fn copy(&self, root: bool) -> OpenWrt {
+        OpenWrt {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            ignore_downloads: self.ignore_downloads,
+            ignore_build: self.ignore_build,
+            root,
+        }
+    }
+
+    /// Save '.config' of the top level directory even if it has been
+    /// filtered.
+    fn dup_config(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            if self.root && d.src_path.join(".config").is_file() {
+                trace!("Backup configuration of {:?}", d.src_path);
+                utils::cp_r_d(&d.src_path, &d.target_path, Path::new(".config"), true)?;
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+impl Flavour for OpenWrt {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "openwrt-ignore", "Ignore OpenWrt directories");
+        opts.optflag("", "openwrt-dl-sync", "Sync OpenWrt download directory");
+        opts.optflag(
+            "",
+            "openwrt-build-sync",
+            "Sync OpenWrt build and output directories",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        OpenWrt {
+            dir: Box::new(None),
+            ignore: args.opt_present("openwrt-ignore"),
+            ignore_downloads: !args.opt_present("openwrt-dl-sync"),
+            ignore_build: !args.opt_present("openwrt-build-sync"),
+            root: false,
+        }
+    }
+
+    /// Look for file 'feeds.conf.default' and directories 'package'
+    /// and 'target' to identify OpenWrt directory.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        let mut m = RequiredFiles::NONE;
+        if d.files
+            .iter()
+            .any(|f| f.file_name().unwrap() == "feeds.conf.default")
+        {
+            m |= RequiredFiles::FEEDS;
+        }
+        for d in &d.dirs {
+            let f = d.file_name().unwrap();
+            if f == "package" {
+                m |= RequiredFiles::PACKAGE;
+            } else if f == "target" {
+                m |= RequiredFiles::TARGET;
+            }
+        }
+        if m == RequiredFiles::ALL {
+            return Some(Box::new(self.copy(true)));
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy(false))
+    }
+
+    fn set_dir(&mut self, mut d: Dir) {
+        // '.config' is in the top level directory and always kept
+        if self.root {
+            let (dl, build) = (self.ignore_downloads, self.ignore_build);
+            d.dirs.retain(|e| {
+                let f = e.file_name().unwrap();
+                !((dl && f == "dl") || (build && BUILD_DIRS.iter().any(|b| f == *b)))
+            });
+        }
+
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Special
+    }
+
+    fn recurse(&self) -> bool {
+        !self.skip()
+    }
+
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    /// The feeds in 'feeds' are probed, they are checked out
+    /// repositories.
+    fn stay(&self) -> bool {
+        !self.dir().as_ref().is_some_and(|d| {
+            d.src_path.file_name() == Some(OsStr::new("feeds"))
+                && d.src_path
+                    .parent()
+                    .is_some_and(|p| p.join("feeds.conf.default").is_file())
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "OpenWrt"
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            d.dup()?;
+            if let Err(e) = self.dup_config() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup configuration because {}", e),
+                });
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            d.merge()?;
+            if let Err(e) = self.dup_config() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup configuration because {}", e),
+                });
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::trace;
+
+use super::utils::SyncError;
+use super::{utils, Category, Dir, Flavour};
+
+/// Cache directories of Python tools.
+const CACHE_DIRS: [&str; 4] = [".tox", "__pycache__", ".mypy_cache", ".pytest_cache"];
+
+/// Packages that 'pip freeze' does not list.
+const FREEZE_SKIP: [&str; 4] = ["pip", "setuptools", "wheel", "distribute"];
+
+pub struct Python {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    freeze: bool,
+    venv: bool,
+}
+
+impl Python {
+    fn copy(&self, venv: bool) -> Python {
+        Python {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            freeze: self.freeze,
+            venv,
+        }
+    }
+
+    /// If only the requirements of a virtual environment are saved.
+    fn freeze_only(&self) -> bool {
+        self.ignore && self.freeze && self.venv
+    }
+
+    /// Write the installed packages of the virtual environment to
+    /// 'requirements.txt' in the format of 'pip freeze'.
+    fn dup_freeze(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            let mut r = String::new();
+            if let Ok(cfg) = fs::read_to_string(d.src_path.join("pyvenv.cfg")) {
+                if let Some(v) = parse_pyvenv_version(&cfg) {
+                    r.push_str(&format!("# Python {}\n", v));
+                }
+            }
+            for (n, v) in freeze(&d.src_path)? {
+                r.push_str(&format!("{}=={}\n", n, v));
+            }
+
+            trace!("Backup requirements of {:?}", d.src_path);
+            fs::write(d.target_path.join("requirements.txt"), r)?;
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+/// Get the Python version from 'pyvenv.cfg'.
+fn parse_pyvenv_version(s: &str) -> Option<String> {
+    s.lines().find_map(|l| {
+        let (k, v) = l.split_once('=')?;
+        matches!(k.trim(), "version" | "version_info").then(|| v.trim().to_string())
+    })
+}
+
+/// Get name and version from the metadata of a distribution.
+fn parse_metadata(s: &str) -> Option<(String, String)> {
+    let (mut name, mut version) = (None, None);
+    for l in s.lines().take_while(|l| !l.is_empty()) {
+        if let Some(n) = l.strip_prefix("Name:") {
+            name = Some(n.trim().to_string());
+        } else if let Some(v) = l.strip_prefix("Version:") {
+            version = Some(v.trim().to_string());
+        }
+    }
+    Some((name?, version?))
+}
+
+/// List name and version of all distributions installed in the
+/// virtual environment 'p', sorted like 'pip freeze' does.
+fn freeze(p: &Path) -> Result<Vec<(String, String)>, SyncError> {
+    let mut site: Vec<PathBuf> = vec![p.join("Lib").join("site-packages")];
+    if let Ok(entries) = fs::read_dir(p.join("lib")) {
+        for e in entries.flatten() {
+            if e.file_name().to_string_lossy().starts_with("python") {
+                site.push(e.path().join("site-packages"));
+            }
+        }
+    }
+
+    let mut r = vec![];
+    for s in site.iter().filter(|s| s.is_dir()) {
+        for e in fs::read_dir(s)?.flatten() {
+            let f = e.file_name().to_string_lossy().to_string();
+            let m = if f.ends_with(".dist-info") {
+                e.path().join("METADATA")
+            } else if f.ends_with(".egg-info") {
+                e.path().join("PKG-INFO")
+            } else {
+                continue;
+            };
+            if let Some((n, v)) = fs::read_to_string(m).ok().and_then(|m| parse_metadata(&m)) {
+                if !FREEZE_SKIP.contains(&n.to_lowercase().as_str()) {
+                    r.push((n, v));
+                }
+            }
+        }
+    }
+    r.sort_by_key(|(n, _)| n.to_lowercase());
+    r.dedup();
+
+    Ok(r)
+}
+
+impl Flavour for Python {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag(
+            "",
+            "python-sync",
+            "Sync Python virtual environments and caches",
+        );
+        opts.optflag(
+            "",
+            "python-freeze",
+            "Save installed packages of Python virtual environments",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Python {
+            dir: Box::new(None),
+            ignore: !args.opt_present("python-sync"),
+            freeze: args.opt_present("python-freeze"),
+            venv: false,
+        }
+    }
+
+    /// Look for file 'pyvenv.cfg' to identify a virtual environment
+    /// or for the name of a cache directory.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        for f in &d.files {
+            if f.file_name().unwrap() == "pyvenv.cfg" {
+                return Some(Box::new(self.copy(true)));
+            }
+        }
+        if let Some(n) = d.src_path.file_name() {
+            if CACHE_DIRS.iter().any(|c| n == *c) {
+                return Some(self.build());
+            }
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy(false))
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Build
+    }
+
+    /// Recurse if --python-sync is set.
+    fn recurse(&self) -> bool {
+        !self.ignore
+    }
+
+    /// Skip if --python-sync is not set unless the requirements of a
+    /// virtual environment shall be saved.
+    fn skip(&self) -> bool {
+        self.ignore && !self.freeze_only()
+    }
+
+    fn name(&self) -> &'static str {
+        "Python"
+    }
+
+    /// Remove an old backup of the virtual environment if only the
+    /// requirements are saved.
+    fn prepare(&mut self) -> Result<(), SyncError> {
+        let freeze_only = self.freeze_only();
+        if let Some(d) = self.dir_mut() {
+            d.ensure_target_path()?;
+            if freeze_only {
+                utils::rm_dirs_and_files(&d.target_path)?;
+            }
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot prepare synchronization without directory".to_string(),
+            ))
+        }
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if self.freeze_only() {
+            self.dup_freeze()
+        } else if let Some(d) = self.dir() {
+            d.dup()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if self.freeze_only() {
+            self.dup_freeze()
+        } else if let Some(d) = self.dir() {
+            d.merge()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_metadata() {
+        let m = "Metadata-Version: 2.1\nName: requests\nVersion: 2.31.0\n\nName: body\n";
+        assert_eq!(
+            parse_metadata(m),
+            Some(("requests".to_string(), "2.31.0".to_string()))
+        );
+        assert_eq!(parse_metadata("Name: x\n"), None);
+        assert_eq!(
+            parse_pyvenv_version("home = /usr/bin\nversion = 3.11.4\n"),
+            Some("3.11.4".to_string())
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::Path;
+
+use git2::Repository;
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour, Git};
+
+pub struct Repo {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    /// The directory is a project with its '.git' linked into
+    /// '.repo/projects'.
+    project: bool,
+    /// Template for the projects.
+    git: Git,
+}
+
+/// Projects have '.git' as symlink into '.repo/projects' which is not
+/// seen by the Git flavour.
+fn is_project(p: &Path) -> bool {
+    let g = p.join(".git");
+    fs::symlink_metadata(&g).is_ok_and(|m| m.file_type().is_symlink())
+        && g.is_dir()
+        && Repository::open(p).is_ok()
+}
+
+impl Repo {
+    fn copy(&self) -> Repo {
+        Repo {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            project: false,
+            git: self.git.copy(),
+        }
+    }
+
+    /// Copy the manifests to '.repo' in the target directory and
+    /// record url, branch and revision of the manifest repository.
+    /// The object stores and project repositories in '.repo' are not
+    /// copied, the projects are backed up as Git repositories.
+    fn dup_manifest(&self) -> Result<(), SyncError> {
+        let d = match self.dir() {
+            Some(d) => d,
+            None => {
+                return Err(SyncError::Failed(
+                    "Cannot synchronize without directory".to_string(),
+                ))
+            }
+        };
+        let s = d.src_path.as_path().join(".repo");
+        let t = d.target_path.as_path().join(".repo");
+        utils::create_dir_save(&t, true)?;
+
+        // usually a symlink or an include of a file in 'manifests'
+        if s.join("manifest.xml").is_file() {
+            trace!("Backup manifest of {:?}", d.src_path);
+            fs::copy(s.join("manifest.xml"), t.join("manifest.xml"))?;
+        }
+
+        for n in ["manifests", "local_manifests"] {
+            if !s.join(n).is_dir() {
+                continue;
+            }
+            for e in fs::read_dir(s.join(n))?.flatten() {
+                let ft = e.file_type()?;
+                if e.file_name() == ".git" {
+                    continue;
+                } else if ft.is_dir() {
+                    utils::cp_tree(&e.path(), &t.join(n).join(e.file_name()), true)?;
+                } else if ft.is_file() {
+                    utils::cp_r_d(&s, &t, &Path::new(n).join(e.file_name()), true)?;
+                }
+            }
+        }
+
+        let m = t.join("manifest");
+        let r = Repository::open(s.join("manifests"))?;
+        let cfg = r.config()?;
+        if let Ok(url) = cfg.get_string("remote.origin.url") {
+            utils::manifest_add(&m, &["url", &url])?;
+        }
+        if let Ok(b) = cfg.get_string("branch.default.merge") {
+            utils::manifest_add(&m, &["branch", &b])?;
+        }
+        if let Some(oid) = r.head()?.target() {
+            utils::manifest_add(&m, &["revision", &oid.to_string()])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Flavour for Repo {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "repo-ignore", "Ignore repo tool workspaces");
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Repo {
+            dir: Box::new(None),
+            ignore: args.opt_present("repo-ignore"),
+            project: false,
+            git: Git::template(args),
+        }
+    }
+
+    /// Look for '.repo' with manifest to identify a workspace of the
+    /// repo tool or for a symlinked '.git' to identify a project,
+    /// projects are left to other types with --git-ignore or
+    /// --git-full.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        for d in &d.dirs {
+            if d.file_name().unwrap() == ".repo"
+                && (d.join("manifest.xml").exists() || d.join("manifests").is_dir())
+            {
+                return Some(self.build());
+            }
+        }
+        if !self.git.skip() && !self.git.recurse() && is_project(&d.src_path) {
+            let mut r = self.copy();
+            r.project = true;
+            return Some(Box::new(r));
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy())
+    }
+
+    fn set_dir(&mut self, mut d: Dir) {
+        // the object stores are not scanned, the manifest is
+        // copied separately
+        d.dirs.retain(|e| e.file_name().unwrap() != ".repo");
+
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Special
+    }
+
+    /// Projects are not scanned, they are backed up as Git
+    /// repositories.
+    fn recurse(&self) -> bool {
+        !self.skip() && !self.project
+    }
+
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    /// Projects are probed.
+    fn stay(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &'static str {
+        "Repo"
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            if self.project {
+                return self
+                    .git
+                    .dup_nested(d.sub_dir(d.src_path.clone(), d.target_path.clone()));
+            }
+            if let Err(e) = self.dup_manifest() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup manifest because {}", e),
+                });
+            }
+            d.dup()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            if self.project {
+                return self
+                    .git
+                    .dup_nested(d.sub_dir(d.src_path.clone(), d.target_path.clone()));
+            }
+            if let Err(e) = self.dup_manifest() {
+                d.send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup manifest because {}", e),
+                });
+            }
+            d.merge()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
@@ -0,0 +1,475 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::Patch;
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour};
+
+mod wc;
+use wc::{External, Translation, Wc};
+
+pub struct Svn {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    full: bool,
+    ignore_unversioned: bool,
+    ignore_modified: bool,
+    modified_patch: bool,
+    modified: Vec<PathBuf>,
+    /// Modified files with their pristine copy and translation for
+    /// the patch.
+    patched: Vec<(PathBuf, PathBuf, Translation)>,
+    unversioned: Vec<PathBuf>,
+    states: Vec<(Reason, PathBuf)>,
+    /// Url and revision of the working copy root.
+    base: Option<(String, i64)>,
+    externals: Vec<External>,
+    /// Changelists with their member paths.
+    changelists: Vec<(String, PathBuf)>,
+    probed: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Reason {
+    Modified,
+    Unversioned,
+    Added,
+    Deleted,
+    Replaced,
+    Conflicted,
+    Missing,
+}
+
+impl Reason {
+    /// Name of state as written to the manifest.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Reason::Modified => "modified",
+            Reason::Unversioned => "unversioned",
+            Reason::Added => "added",
+            Reason::Deleted => "deleted",
+            Reason::Replaced => "replaced",
+            Reason::Conflicted => "conflicted",
+            Reason::Missing => "missing",
+        }
+    }
+}
+
+impl Svn {
+    /// Create new flavour with the same options but without [Dir].
+    fn copy(&self, probed: bool) -> Svn {
+        Svn {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            full: self.full,
+            ignore_unversioned: self.ignore_unversioned,
+            ignore_modified: self.ignore_modified,
+            modified_patch: self.modified_patch,
+            modified: vec![],
+            patched: vec![],
+            unversioned: vec![],
+            states: vec![],
+            base: None,
+            externals: vec![],
+            changelists: vec![],
+            probed,
+        }
+    }
+
+    fn dir_unchecked(&self) -> &Dir {
+        match self.dir.as_ref() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
+    fn dir_unchecked_mut(&mut self) -> &mut Dir {
+        match self.dir.as_mut() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
+    fn subdir_create(&self, n: &str) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let p = &d.target_path.as_path().join(n);
+        utils::create_dir_save(p, true)?;
+        Ok(())
+    }
+
+    fn subdir_rename(&self, n: &str, s: &str) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let p = &d.target_path.as_path().join(n);
+        if p.exists() {
+            fs::remove_dir_all(p)?;
+        }
+        fs::File::create(d.target_path.as_path().join(format!("{}.{}", n, s)))?;
+        Ok(())
+    }
+
+    fn subdir_ignored(&self, n: &str) -> Result<(), SyncError> {
+        self.subdir_rename(n, "ignored")
+    }
+
+    fn subdir_empty(&self, n: &str) -> Result<(), SyncError> {
+        self.subdir_rename(n, "empty")
+    }
+
+    /// Target path of working copy root 'root' for a directory with
+    /// source path 's' and target path 't' as given by the scanner.
+    /// Externals are kept in 'externals' and nested working copies
+    /// in 'unversioned' of the working copy that contains them.
+    fn root_target(s: &Path, t: &Path, root: &Path) -> PathBuf {
+        if let Some(proot) = root.parent().and_then(Wc::find_root) {
+            let rp = root.strip_prefix(&proot).unwrap();
+            let section = match Wc::open(&proot).and_then(|wc| wc.is_external(rp)) {
+                Ok(true) => "externals",
+                _ => "unversioned",
+            };
+            return Self::root_target(s, t, &proot).join(section).join(rp);
+        }
+
+        let mut t = t.to_path_buf();
+        for _ in s.strip_prefix(root).unwrap() {
+            t.pop();
+        }
+        t
+    }
+
+    /// Move target path of unversioned directory below
+    /// 'unversioned' of the working copy root.
+    fn modify_target_path(&mut self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked_mut();
+        let root = match Wc::find_root(&d.src_path) {
+            Some(r) => r,
+            None => {
+                return Err(SyncError::Failed(format!(
+                    "Failed to find SVN root for {:?}",
+                    d.src_path
+                )))
+            }
+        };
+
+        let pp = d.src_path.strip_prefix(&root).unwrap().to_path_buf();
+        d.target_path = Self::root_target(&d.src_path, &d.target_path, &root)
+            .join("unversioned")
+            .join(pp);
+
+        Ok(())
+    }
+
+    /// Read the state of the working copy from its database.
+    fn prepare_contents(&mut self) -> Result<(), SyncError> {
+        let (category, name) = (self.category(), self.name().to_string());
+        let (states, wc) = {
+            let d = self.dir_unchecked_mut();
+            d.target_path = Self::root_target(&d.src_path, &d.target_path, &d.src_path);
+            d.dirs.clear();
+            d.files.clear();
+            d.ex_dirs.clear();
+            d.ex_files.clear();
+            if d.target_path.exists() {
+                utils::rm_dirs_and_files(d.target_path.as_path())?;
+            }
+
+            match Wc::open(&d.src_path).and_then(|wc| Ok((wc.status()?, wc))) {
+                Ok(s) => s,
+                Err(e) => {
+                    d.send_runtime(stats::Info {
+                        category,
+                        name,
+                        desc: format!("Cannot read working copy {:?} because {}", d.src_path, e),
+                    });
+                    return Err(e);
+                }
+            }
+        };
+
+        let src = self.dir_unchecked().src_path.clone();
+        self.base = wc.base()?;
+        self.changelists = wc.changelists()?;
+        for e in wc.externals()? {
+            let f = src.join(&e.path);
+            // directory externals are working copies of their own
+            if f.is_dir() && Wc::find_root(&f).as_ref() == Some(&f) {
+                self.dir_unchecked_mut().dirs.push(f);
+            }
+            self.externals.push(e);
+        }
+        for (r, rp) in states {
+            let f = src.join(&rp);
+            match r {
+                Reason::Modified if self.ignore_modified || !f.is_file() => (),
+                Reason::Modified if self.modified_patch => match wc.pristine_path(&rp)? {
+                    Some((p, t)) => self.patched.push((f, p, t)),
+                    None => self.modified.push(f),
+                },
+                Reason::Modified => self.modified.push(f),
+                Reason::Unversioned => {
+                    if self.ignore_unversioned {
+                        continue;
+                    }
+                    if fs::symlink_metadata(&f)?.is_dir() {
+                        // scanned as directory of its own
+                        self.dir_unchecked_mut().dirs.push(f);
+                    } else {
+                        self.unversioned.push(f);
+                    }
+                }
+                Reason::Added | Reason::Replaced | Reason::Conflicted => {
+                    // the content of these is not in the repository,
+                    // keep it with the modified files
+                    if !self.ignore_modified && f.is_file() {
+                        self.modified.push(f.clone());
+                    }
+                    self.states.push((r, f));
+                }
+                Reason::Deleted | Reason::Missing => self.states.push((r, f)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write unified diff of modified files against their pristine
+    /// copies to 'modified.patch', returns the binary files that
+    /// cannot be patched. The modified files are converted to the
+    /// repository normal form first like the pristine copies.
+    fn dup_patch(&self) -> Result<Vec<&PathBuf>, SyncError> {
+        let d = self.dir_unchecked();
+        let mut b: Vec<u8> = Vec::new();
+        let mut binary = Vec::new();
+
+        for (f, p, t) in &self.patched {
+            let rp = f.strip_prefix(&d.src_path).unwrap_or(f);
+            let (old, new) = (fs::read(p)?, t.detranslate(&fs::read(f)?));
+            let mut patch = Patch::from_buffers(&old, Some(rp), &new, Some(rp), None)?;
+            if patch.delta().flags().is_binary() {
+                trace!("Binary file {:?} cannot be patched", f);
+                binary.push(f);
+                continue;
+            }
+
+            trace!("Backup modified {:?} as patch", f);
+            b.extend_from_slice(&patch.to_buf()?);
+        }
+
+        fs::write(d.target_path.as_path().join("modified.patch"), b)?;
+        Ok(binary)
+    }
+
+    fn dup_all(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            // unversioned directories have been prepared already
+            utils::rm_dirs_and_files_except(
+                d.target_path.as_path(),
+                &["unversioned", "externals"],
+            )?;
+
+            let m = d.target_path.as_path().join("manifest");
+            if let Some((url, rev)) = &self.base {
+                utils::manifest_add(&m, &["url", url])?;
+                utils::manifest_add(&m, &["revision", &rev.to_string()])?;
+            }
+            for (r, f) in &self.states {
+                let p = f.strip_prefix(&d.src_path).unwrap_or(f);
+                utils::manifest_add(&m, &[r.as_str(), &p.to_string_lossy()])?;
+            }
+            for e in &self.externals {
+                let rev = e.revision.map_or("HEAD".to_string(), |r| r.to_string());
+                utils::manifest_add(&m, &["external", &e.path.to_string_lossy(), &e.url, &rev])?;
+            }
+            for (c, p) in &self.changelists {
+                utils::manifest_add(&m, &["changelist", c, &p.to_string_lossy()])?;
+            }
+
+            // binary files cannot be patched, they are copied
+            let mut modified: Vec<&PathBuf> = self.modified.iter().collect();
+            if !self.patched.is_empty() {
+                modified.append(&mut self.dup_patch()?);
+            }
+
+            self.subdir_create("modified")?;
+            if self.ignore_modified {
+                self.subdir_ignored("modified")?;
+            } else if modified.is_empty() {
+                self.subdir_empty("modified")?;
+            } else {
+                for f in modified {
+                    trace!("Backup modified {:?}", f);
+                    utils::cp_d(
+                        d.src_path.as_path(),
+                        &d.target_path.as_path().join("modified"),
+                        f,
+                        true,
+                    )?;
+                }
+            }
+
+            utils::create_dir_save(&d.target_path.as_path().join("unversioned"), false)?;
+            if self.ignore_unversioned {
+                self.subdir_ignored("unversioned")?;
+            } else if self.unversioned.is_empty() && d.dirs.is_empty() {
+                self.subdir_empty("unversioned")?;
+            } else {
+                for f in &self.unversioned {
+                    trace!("Backup unversioned {:?}", f);
+                    utils::cp_d(
+                        d.src_path.as_path(),
+                        &d.target_path.as_path().join("unversioned"),
+                        f,
+                        true,
+                    )?;
+                }
+            }
+
+            Ok(())
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+impl Flavour for Svn {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "svn-ignore", "Ignore Svn repositories");
+        opts.optflag(
+            "",
+            "svn-full",
+            "Full backup (default is unversioned and modified)",
+        );
+        opts.optflag(
+            "",
+            "svn-ignore-unversioned",
+            "Don't backup unversioned files",
+        );
+        opts.optflag("", "svn-ignore-modified", "Don't backup modified files");
+        opts.optflag(
+            "",
+            "svn-modified-patch",
+            "Backup modified files as patch against BASE",
+        );
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        Svn {
+            dir: Box::new(None),
+            ignore: args.opt_present("svn-ignore"),
+            full: args.opt_present("svn-full"),
+            ignore_unversioned: args.opt_present("svn-ignore-unversioned"),
+            ignore_modified: args.opt_present("svn-ignore-modified"),
+            modified_patch: args.opt_present("svn-modified-patch"),
+            modified: vec![],
+            patched: vec![],
+            unversioned: vec![],
+            states: vec![],
+            base: None,
+            externals: vec![],
+            changelists: vec![],
+            probed: false,
+        }
+    }
+
+    /// Look for file '.svn' to identify Subversion directory.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        for d in &d.dirs {
+            if d.file_name().unwrap() == ".svn" {
+                return Some(Box::new(self.copy(true)));
+            }
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy(false))
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn name(&self) -> &'static str {
+        "Subversion"
+    }
+
+    fn category(&self) -> Category {
+        Category::Repository
+    }
+
+    /// Recurse if --svn-ignore is not set.
+    fn recurse(&self) -> bool {
+        !self.ignore
+    }
+
+    /// Recurse if --svn-ignore is set.
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    fn stay(&self) -> bool {
+        !self.full
+    }
+
+    /// Prepare for backup. Default implementations simply creates the
+    /// target directory.
+    fn prepare(&mut self) -> Result<(), SyncError> {
+        if self.dir().is_some() {
+            if !self.full {
+                // nested working copies are scanned like unversioned
+                // directories
+                let src = &self.dir_unchecked().src_path;
+                if self.probed || Wc::find_root(src).as_ref() == Some(src) {
+                    self.probed = true;
+                    self.prepare_contents()?;
+                } else {
+                    self.modify_target_path()?;
+                }
+            }
+            self.dir_unchecked_mut().ensure_target_path()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot prepare synchronization without directory".to_string(),
+            ))
+        }
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if !self.full && self.probed {
+            self.dup_all()
+        } else if let Some(d) = self.dir() {
+            d.dup()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if !self.full && self.probed {
+            self.dup_all()
+        } else if let Some(d) = self.dir() {
+            d.merge()
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
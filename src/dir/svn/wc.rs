@@ -0,0 +1,713 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use log::trace;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+use super::super::utils::{self, SyncError};
+use super::Reason;
+
+/// Oldest supported working copy format (Subversion 1.7).
+const FORMAT_MIN: i32 = 29;
+/// Newest supported working copy format (Subversion 1.8 - 1.14).
+const FORMAT_MAX: i32 = 31;
+
+/// Default of the 'global-ignores' runtime configuration if neither
+/// the user nor the system configuration set it.
+const GLOBAL_IGNORES: &[&str] = &[
+    "*.o",
+    "*.lo",
+    "*.la",
+    "*.al",
+    ".libs",
+    "*.so",
+    "*.so.[0-9]*",
+    "*.a",
+    "*.pyc",
+    "*.pyo",
+    "__pycache__",
+    "*.rej",
+    "*~",
+    "#*#",
+    ".#*",
+    ".*.swp",
+    ".DS_Store",
+    "[Tt]humbs.db",
+];
+
+/// Keywords that are expanded for the same value.
+const KEYWORD_ALIASES: [&[&str]; 6] = [
+    &["LastChangedDate", "Date"],
+    &["LastChangedRevision", "Rev", "Revision"],
+    &["LastChangedBy", "Author"],
+    &["HeadURL", "URL"],
+    &["Id"],
+    &["Header"],
+];
+
+/// Translation of a versioned file between the repository normal
+/// form and the working file, set by 'svn:keywords' and
+/// 'svn:eol-style'.
+#[derive(Default)]
+pub struct Translation {
+    /// Keywords that are expanded including their aliases.
+    keywords: Vec<String>,
+    /// Line endings are translated.
+    eol: bool,
+}
+
+impl Translation {
+    /// Get translation from the properties skel of a node.
+    fn from_props(props: Option<&[u8]>) -> Self {
+        let mut props = props.map(skel_props).unwrap_or_default();
+        let mut keywords = Vec::new();
+        if let Some(v) = props.remove("svn:keywords") {
+            for k in String::from_utf8_lossy(&v).split_whitespace() {
+                // custom keywords are defined as 'name=format'
+                let k = k.split_once('=').map_or(k, |(k, _)| k);
+                match KEYWORD_ALIASES.iter().find(|a| a.contains(&k)) {
+                    Some(a) => keywords.extend(a.iter().map(|k| k.to_string())),
+                    None => keywords.push(k.to_string()),
+                }
+            }
+        }
+
+        Translation {
+            keywords,
+            eol: props.contains_key("svn:eol-style"),
+        }
+    }
+
+    /// Is the working file the same as the repository normal form?
+    fn is_identity(&self) -> bool {
+        self.keywords.is_empty() && !self.eol
+    }
+
+    /// Convert working file content 'b' to the repository normal form,
+    /// keywords are contracted and line endings are converted to LF.
+    pub fn detranslate(&self, b: &[u8]) -> Vec<u8> {
+        let mut b = b.to_vec();
+        if self.eol {
+            let mut n = Vec::with_capacity(b.len());
+            let mut i = 0;
+            while i < b.len() {
+                match b[i] {
+                    b'\r' if b.get(i + 1) == Some(&b'\n') => (),
+                    b'\r' => n.push(b'\n'),
+                    c => n.push(c),
+                }
+                i += 1;
+            }
+            b = n;
+        }
+        if !self.keywords.is_empty() {
+            b = self.contract_keywords(&b);
+        }
+        b
+    }
+
+    /// Contract '$Name: value $' to '$Name$' and the fixed length form
+    /// '$Name:: value $' to '$Name::       $'.
+    fn contract_keywords(&self, b: &[u8]) -> Vec<u8> {
+        let mut n = Vec::with_capacity(b.len());
+        let mut i = 0;
+        while i < b.len() {
+            if b[i] != b'$' {
+                n.push(b[i]);
+                i += 1;
+                continue;
+            }
+
+            // keywords do not span lines and are limited to 255 bytes
+            let end = b[i + 1..]
+                .iter()
+                .take(255)
+                .position(|c| *c == b'$' || *c == b'\n')
+                .map(|e| i + 1 + e)
+                .filter(|e| b[*e] == b'$');
+            let e = match end {
+                Some(e) => e,
+                None => {
+                    n.push(b'$');
+                    i += 1;
+                    continue;
+                }
+            };
+
+            let c = &b[i + 1..e];
+            let name = c.split(|c| *c == b':').next().unwrap();
+            match self.keywords.iter().find(|k| k.as_bytes() == name) {
+                Some(k) if c[k.len()..].starts_with(b":: ") && c.len() > k.len() + 3 => {
+                    n.push(b'$');
+                    n.extend_from_slice(k.as_bytes());
+                    n.extend_from_slice(b"::");
+                    n.resize(n.len() + c.len() - k.len() - 2, b' ');
+                    n.push(b'$');
+                    i = e + 1;
+                }
+                Some(k) if c[k.len()..].starts_with(b": ") && c.ends_with(b" ") => {
+                    n.push(b'$');
+                    n.extend_from_slice(k.as_bytes());
+                    n.push(b'$');
+                    i = e + 1;
+                }
+                // the closing '$' may start the next keyword
+                _ => {
+                    n.extend_from_slice(&b[i..e]);
+                    i = e;
+                }
+            }
+        }
+        n
+    }
+}
+
+/// Node of the working copy, this is the row of table NODES with the
+/// highest op_depth.
+struct Node {
+    kind: String,
+    presence: String,
+    op_depth: usize,
+    checksum: Option<String>,
+    translated_size: Option<i64>,
+    last_mod_time: Option<i64>,
+    properties: Option<Vec<u8>>,
+    /// There is a node with lower op_depth that is present.
+    replaces: bool,
+}
+
+/// Definition of an external.
+pub struct External {
+    /// Path relative to the root of the defining working copy.
+    pub path: PathBuf,
+    pub url: String,
+    /// Pinned revision or None for HEAD.
+    pub revision: Option<i64>,
+}
+
+/// Subversion working copy, read from '.svn/wc.db'.
+pub struct Wc {
+    root: PathBuf,
+    db: Connection,
+    wc_id: i64,
+    /// The 'global-ignores' of the runtime configuration.
+    global_ignores: Vec<String>,
+}
+
+impl Wc {
+    /// Find root of working copy that contains path 'p'.
+    pub fn find_root(p: &Path) -> Option<PathBuf> {
+        p.ancestors()
+            .find(|a| a.join(".svn").join("wc.db").is_file())
+            .map(Path::to_path_buf)
+    }
+
+    /// Open working copy with root 'p'.
+    pub fn open(p: &Path) -> Result<Self, SyncError> {
+        let f = p.join(".svn").join("wc.db");
+        if !f.is_file() {
+            return Err(SyncError::Failed(format!(
+                "No working copy database {:?}, formats before Subversion 1.7 are not supported",
+                f
+            )));
+        }
+
+        let db = Connection::open_with_flags(
+            &f,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        let format: i32 = db.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        if !(FORMAT_MIN..=FORMAT_MAX).contains(&format) {
+            return Err(SyncError::Failed(format!(
+                "Working copy format {} of {:?} is not supported",
+                format, p
+            )));
+        }
+        let wc_id = db.query_row("SELECT id FROM WCROOT", [], |r| r.get(0))?;
+
+        Ok(Wc {
+            root: p.to_path_buf(),
+            db,
+            wc_id,
+            global_ignores: global_ignores(),
+        })
+    }
+
+    /// Read all nodes, the key is the path relative to the root.
+    fn nodes(&self) -> Result<HashMap<String, Node>, SyncError> {
+        let mut stmt = self.db.prepare(
+            "SELECT local_relpath, kind, presence, op_depth, checksum, translated_size,
+                    last_mod_time, properties,
+                    EXISTS (SELECT 1 FROM NODES b
+                            WHERE b.wc_id = n.wc_id AND b.local_relpath = n.local_relpath
+                              AND b.op_depth < n.op_depth AND b.presence = 'normal')
+             FROM NODES n
+             WHERE wc_id = ?1
+               AND op_depth = (SELECT MAX(op_depth) FROM NODES m
+                               WHERE m.wc_id = n.wc_id AND m.local_relpath = n.local_relpath)",
+        )?;
+        let rows = stmt.query_map([self.wc_id], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                Node {
+                    kind: r.get(1)?,
+                    presence: r.get(2)?,
+                    op_depth: r.get::<_, i64>(3)? as usize,
+                    checksum: r.get(4)?,
+                    translated_size: r.get(5)?,
+                    last_mod_time: r.get(6)?,
+                    properties: r.get(7)?,
+                    replaces: r.get(8)?,
+                },
+            ))
+        })?;
+
+        let mut nodes = HashMap::new();
+        for r in rows {
+            let (p, n) = r?;
+            nodes.insert(p, n);
+        }
+
+        // locally changed properties
+        let mut stmt = self.db.prepare(
+            "SELECT local_relpath, properties FROM ACTUAL_NODE
+             WHERE wc_id = ?1 AND properties IS NOT NULL",
+        )?;
+        let rows = stmt.query_map([self.wc_id], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, Vec<u8>>(1)?))
+        })?;
+        for r in rows {
+            let (p, props) = r?;
+            if let Some(n) = nodes.get_mut(&p) {
+                n.properties = Some(props);
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// Paths with text, property or tree conflicts.
+    fn conflicts(&self) -> Result<HashSet<String>, SyncError> {
+        // the conflict columns differ between the formats
+        let mut stmt = self.db.prepare("PRAGMA table_info(ACTUAL_NODE)")?;
+        let columns: Vec<String> = stmt
+            .query_map([], |r| r.get::<_, String>(1))?
+            .collect::<Result<_, _>>()?;
+        let cond: Vec<String> = [
+            "conflict_data",
+            "conflict_old",
+            "conflict_new",
+            "conflict_working",
+            "prop_reject",
+            "tree_conflict_data",
+        ]
+        .iter()
+        .filter(|c| columns.iter().any(|n| n == *c))
+        .map(|c| format!("{} IS NOT NULL", c))
+        .collect();
+        if cond.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT local_relpath FROM ACTUAL_NODE WHERE wc_id = ?1 AND ({})",
+            cond.join(" OR ")
+        ))?;
+        let rows = stmt.query_map([self.wc_id], |r| r.get::<_, String>(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Path of the pristine copy for checksum '$sha1$...'.
+    fn pristine(&self, checksum: &str) -> Option<PathBuf> {
+        let h = checksum.strip_prefix("$sha1$")?;
+        Some(
+            self.root
+                .join(".svn")
+                .join("pristine")
+                .join(h.get(0..2)?)
+                .join(format!("{}.svn-base", h)),
+        )
+    }
+
+    /// Has file 'p' been modified compared to its pristine copy?
+    fn modified(&self, n: &Node, p: &Path, m: &fs::Metadata) -> bool {
+        // same shortcut that Subversion uses
+        let mtime = m.mtime() * 1_000_000 + m.mtime_nsec() / 1_000;
+        if n.translated_size == Some(m.len() as i64) && n.last_mod_time == Some(mtime) {
+            return false;
+        }
+
+        let t = Translation::from_props(n.properties.as_deref());
+        match n.checksum.as_deref().and_then(|c| self.pristine(c)) {
+            Some(pp) => match (fs::read(pp), fs::read(p)) {
+                (Ok(a), Ok(b)) if t.is_identity() => a != b,
+                (Ok(a), Ok(b)) => a != t.detranslate(&b),
+                _ => true,
+            },
+            None => true,
+        }
+    }
+
+    /// Ignore patterns for unversioned entries of directory 'dir'.
+    fn ignores(&self, nodes: &HashMap<String, Node>, dir: &str) -> Vec<String> {
+        let mut patterns = self.global_ignores.clone();
+        let prop = |p: &str, n: &str| -> Vec<String> {
+            nodes
+                .get(p)
+                .and_then(|n| n.properties.as_deref())
+                .and_then(|b| skel_props(b).remove(n))
+                .map(|v| {
+                    String::from_utf8_lossy(&v)
+                        .split_whitespace()
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        patterns.append(&mut prop(dir, "svn:ignore"));
+        let mut a = Some(dir);
+        while let Some(p) = a {
+            patterns.append(&mut prop(p, "svn:global-ignores"));
+            a = match p {
+                "" => None,
+                p => Some(p.rsplit_once('/').map_or("", |(a, _)| a)),
+            };
+        }
+
+        patterns
+    }
+
+    /// Get url and revision of the working copy root.
+    pub fn base(&self) -> Result<Option<(String, i64)>, SyncError> {
+        let mut stmt = self.db.prepare(
+            "SELECT r.root, n.repos_path, n.revision FROM NODES n
+             JOIN REPOSITORY r ON r.id = n.repos_id
+             WHERE n.wc_id = ?1 AND n.local_relpath = '' AND n.op_depth = 0",
+        )?;
+        let mut rows = stmt.query([self.wc_id])?;
+        match rows.next()? {
+            Some(r) => {
+                let (root, rp): (String, String) = (r.get(0)?, r.get(1)?);
+                let url = match rp.as_str() {
+                    "" => root,
+                    rp => format!("{}/{}", root.trim_end_matches('/'), rp),
+                };
+                Ok(Some((url, r.get(2)?)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Get all externals defined in this working copy.
+    pub fn externals(&self) -> Result<Vec<External>, SyncError> {
+        let mut stmt = self.db.prepare(
+            "SELECT e.local_relpath, r.root, e.def_repos_relpath, e.def_revision
+             FROM EXTERNALS e JOIN REPOSITORY r ON r.id = e.repos_id
+             WHERE e.wc_id = ?1 AND e.presence = 'normal'",
+        )?;
+        let rows = stmt.query_map([self.wc_id], |r| {
+            let root: String = r.get(1)?;
+            Ok(External {
+                path: PathBuf::from(r.get::<_, String>(0)?),
+                url: format!("{}/{}", root.trim_end_matches('/'), r.get::<_, String>(2)?),
+                // the column has text affinity
+                revision: match r.get_ref(3)? {
+                    ValueRef::Integer(i) => Some(i),
+                    ValueRef::Text(t) => std::str::from_utf8(t).ok().and_then(|t| t.parse().ok()),
+                    _ => None,
+                },
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Is 'rp' an external of this working copy?
+    pub fn is_external(&self, rp: &Path) -> Result<bool, SyncError> {
+        Ok(self
+            .db
+            .query_row(
+                "SELECT 1 FROM EXTERNALS WHERE wc_id = ?1 AND local_relpath = ?2",
+                params![self.wc_id, rp.to_str()],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    /// Get changelists with their member paths.
+    pub fn changelists(&self) -> Result<Vec<(String, PathBuf)>, SyncError> {
+        let mut stmt = self.db.prepare(
+            "SELECT changelist, local_relpath FROM ACTUAL_NODE
+             WHERE wc_id = ?1 AND changelist IS NOT NULL ORDER BY changelist, local_relpath",
+        )?;
+        let rows = stmt.query_map([self.wc_id], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                PathBuf::from(r.get::<_, String>(1)?),
+            ))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Get pristine copy of versioned file 'rp' and the translation
+    /// of the working file.
+    pub fn pristine_path(&self, rp: &Path) -> Result<Option<(PathBuf, Translation)>, SyncError> {
+        let row: Option<(Option<String>, Option<Vec<u8>>)> = self
+            .db
+            .query_row(
+                "SELECT checksum, properties FROM NODES
+                 WHERE wc_id = ?1 AND local_relpath = ?2 ORDER BY op_depth DESC LIMIT 1",
+                params![self.wc_id, rp.to_str()],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()?;
+        let (checksum, props) = match row {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+
+        // locally changed properties
+        let actual: Option<Vec<u8>> = self
+            .db
+            .query_row(
+                "SELECT properties FROM ACTUAL_NODE
+                 WHERE wc_id = ?1 AND local_relpath = ?2 AND properties IS NOT NULL",
+                params![self.wc_id, rp.to_str()],
+                |r| r.get(0),
+            )
+            .optional()?;
+
+        let t = Translation::from_props(actual.or(props).as_deref());
+        Ok(checksum
+            .and_then(|c| self.pristine(&c))
+            .filter(|p| p.is_file())
+            .map(|p| (p, t)))
+    }
+
+    /// Get states of all paths that differ from the pristine working
+    /// copy, the paths are relative to the root.
+    pub fn status(&self) -> Result<Vec<(Reason, PathBuf)>, SyncError> {
+        let nodes = self.nodes()?;
+        let conflicts = self.conflicts()?;
+        let mut states = Vec::new();
+
+        for (rp, n) in &nodes {
+            if rp.is_empty() {
+                continue;
+            }
+            let p = self.root.join(rp);
+            // the root of an add, copy or replace operation
+            let op_root = n.op_depth > 0 && n.op_depth == rp.split('/').count();
+
+            let r = if conflicts.contains(rp) {
+                Reason::Conflicted
+            } else if n.presence == "base-deleted"
+                || (n.op_depth > 0 && n.presence == "not-present")
+            {
+                Reason::Deleted
+            } else if n.presence != "normal" {
+                continue;
+            } else {
+                match fs::symlink_metadata(&p) {
+                    Err(_) => Reason::Missing,
+                    Ok(_) if op_root && n.replaces => Reason::Replaced,
+                    Ok(_) if op_root => Reason::Added,
+                    Ok(m) if n.kind == "file" && m.is_file() && self.modified(n, &p, &m) => {
+                        Reason::Modified
+                    }
+                    Ok(_) => continue,
+                }
+            };
+
+            trace!("Node {} is {}", rp, r.as_str());
+            states.push((r, PathBuf::from(rp)));
+        }
+
+        let externals: HashSet<String> = self
+            .externals()?
+            .into_iter()
+            .filter_map(|e| e.path.to_str().map(String::from))
+            .collect();
+        self.unversioned(&nodes, &externals, "", &mut states)?;
+
+        Ok(states)
+    }
+
+    /// Find unversioned entries in versioned directory 'dir', neither
+    /// ignored nor unversioned directories are descended into and
+    /// externals are not unversioned.
+    fn unversioned(
+        &self,
+        nodes: &HashMap<String, Node>,
+        externals: &HashSet<String>,
+        dir: &str,
+        states: &mut Vec<(Reason, PathBuf)>,
+    ) -> Result<(), SyncError> {
+        let ignores = self.ignores(nodes, dir);
+
+        for e in fs::read_dir(self.root.join(dir))?.flatten() {
+            let name = e.file_name().to_string_lossy().to_string();
+            if dir.is_empty() && name == ".svn" {
+                continue;
+            }
+
+            let rp = match dir {
+                "" => name.clone(),
+                d => format!("{}/{}", d, name),
+            };
+            match nodes.get(&rp) {
+                Some(n) if n.kind == "dir" && n.presence == "normal" => {
+                    if e.file_type()?.is_dir() {
+                        self.unversioned(nodes, externals, &rp, states)?;
+                    }
+                }
+                Some(n) if n.presence == "normal" || n.presence == "base-deleted" => (),
+                _ if externals.contains(&rp) => (),
+                _ => {
+                    if !ignores
+                        .iter()
+                        .any(|g| utils::glob_match(g, Path::new(&name)))
+                    {
+                        trace!("Node {} is unversioned", rp);
+                        states.push((Reason::Unversioned, PathBuf::from(rp)));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get 'global-ignores' from section 'miscellany' of the runtime
+/// configuration 'c', values may continue on indented lines.
+fn config_global_ignores(c: &str) -> Option<Vec<String>> {
+    let mut section = "";
+    let mut value: Option<String> = None;
+    for l in c.lines() {
+        if l.starts_with('#') {
+            continue;
+        }
+        if let Some(v) = value.as_mut() {
+            if l.starts_with([' ', '\t']) && !l.trim().is_empty() {
+                v.push(' ');
+                v.push_str(l.trim());
+                continue;
+            }
+            break;
+        }
+
+        let l = l.trim();
+        if let Some(s) = l.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            section = s;
+        } else if let Some((k, v)) = l.split_once(['=', ':']) {
+            if section == "miscellany" && k.trim() == "global-ignores" {
+                value = Some(v.trim().to_string());
+            }
+        }
+    }
+
+    value.map(|v| v.split_whitespace().map(String::from).collect())
+}
+
+/// Get 'global-ignores' from the user or the system configuration or
+/// the default.
+fn global_ignores() -> Vec<String> {
+    let mut configs = vec![PathBuf::from("/etc/subversion/config")];
+    if let Some(h) = std::env::var_os("HOME") {
+        configs.insert(0, Path::new(&h).join(".subversion").join("config"));
+    }
+
+    configs
+        .iter()
+        .filter_map(|c| fs::read_to_string(c).ok())
+        .find_map(|c| config_global_ignores(&c))
+        .unwrap_or_else(|| GLOBAL_IGNORES.iter().map(|p| p.to_string()).collect())
+}
+
+/// Parse the properties skel '(name value name value ...)' into a map.
+fn skel_props(b: &[u8]) -> HashMap<String, Vec<u8>> {
+    let mut atoms: Vec<Vec<u8>> = Vec::new();
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'(' | b')' | b' ' | b'\t' | b'\n' | b'\r' => i += 1,
+            // explicit length atom
+            c if c.is_ascii_digit() => {
+                let s = i;
+                while i < b.len() && b[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let l: usize = match std::str::from_utf8(&b[s..i])
+                    .ok()
+                    .and_then(|l| l.parse().ok())
+                {
+                    Some(l) => l,
+                    None => break,
+                };
+                i += 1;
+                if i + l > b.len() {
+                    break;
+                }
+                atoms.push(b[i..i + l].to_vec());
+                i += l;
+            }
+            // implicit length atom
+            _ => {
+                let s = i;
+                while i < b.len() && !b" \t\n\r()".contains(&b[i]) {
+                    i += 1;
+                }
+                atoms.push(b[s..i].to_vec());
+            }
+        }
+    }
+
+    atoms
+        .chunks_exact(2)
+        .map(|c| (String::from_utf8_lossy(&c[0]).to_string(), c[1].clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_skel_props() {
+        let p = skel_props(b"(svn:ignore 10 *.o\nbuild\n 12 svn:keywords 2 Id)");
+        assert_eq!(p.get("svn:ignore").unwrap(), b"*.o\nbuild\n");
+        assert_eq!(p.get("svn:keywords").unwrap(), b"Id");
+        assert!(skel_props(b"()").is_empty());
+    }
+
+    #[test]
+    fn test_detranslate() {
+        let t = Translation::from_props(Some(b"(svn:keywords 6 Id Rev svn:eol-style 6 native)"));
+        assert!(!t.is_identity());
+        assert_eq!(
+            t.detranslate(b"$Id: a.c 5 2024-01-01 jh $\r\n$Revision: 5 $\r\n$Author: jh $\r\n"),
+            b"$Id$\n$Revision$\n$Author: jh $\n"
+        );
+        assert_eq!(
+            t.detranslate(b"$Rev:: 5    $ $ $Id$"),
+            b"$Rev::      $ $ $Id$"
+        );
+        assert!(Translation::from_props(Some(b"(svn:ignore 2 *o)")).is_identity());
+    }
+
+    #[test]
+    fn test_config_global_ignores() {
+        let c = "[auth]\nglobal-ignores = x\n[miscellany]\n# global-ignores = y\nglobal-ignores = *.o\n  build\nenable-auto-props = yes\n";
+        assert_eq!(config_global_ignores(c).unwrap(), vec!["*.o", "build"]);
+        assert!(config_global_ignores("[miscellany]\n").is_none());
+    }
+}
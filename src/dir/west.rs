@@ -0,0 +1,415 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fs;
+use std::path::Path;
+
+use git2::{BranchType, Oid, Repository, StatusOptions};
+use log::trace;
+
+use super::utils::SyncError;
+use super::{stats, utils, Category, Dir, Flavour, Git};
+
+/// What a directory inside a west workspace is.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    /// Not yet known, set in [Flavour::prepare].
+    Unknown,
+    /// The workspace root with '.west/config'.
+    Workspace,
+    /// A module with a manifest revision, 'true' if pristine.
+    Module(bool),
+}
+
+pub struct West {
+    dir: Box<Option<Dir>>,
+    ignore: bool,
+    kind: Kind,
+    /// Template for modules with local changes.
+    git: Git,
+}
+
+impl West {
+    fn copy(&self) -> West {
+        West {
+            dir: Box::new(None),
+            ignore: self.ignore,
+            kind: Kind::Unknown,
+            git: self.git.copy(),
+        }
+    }
+
+    fn dir_unchecked(&self) -> &Dir {
+        match self.dir.as_ref() {
+            Some(d) => d,
+            None => panic!("Flavours 'dir' entry is None"),
+        }
+    }
+
+    /// Copy '.west/config' and the manifest file to '.west' in the
+    /// target directory and record the manifest repository. This
+    /// happens before the modules are scanned, they add their records
+    /// in [West::record_module].
+    fn dup_workspace(&self) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let s = d.src_path.as_path();
+        let t = d.target_path.as_path().join(".west");
+        utils::create_dir_save(&t, true)?;
+        utils::cp_r(&s.join(".west"), &t, Path::new("config"), true)?;
+
+        let m = t.join("manifest");
+        let (path, file) = parse_config(&fs::read_to_string(s.join(".west").join("config"))?);
+        if let Some(path) = path {
+            let file = file.unwrap_or("west.yml".to_string());
+            let rev = Repository::open(s.join(&path))
+                .ok()
+                .and_then(|r| r.head().ok()?.target())
+                .map_or(String::new(), |oid| oid.to_string());
+            utils::manifest_add(&m, &["manifest", &path, &file, &rev])?;
+            if s.join(&path).join(&file).is_file() {
+                fs::copy(s.join(&path).join(&file), t.join(&file))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record module with its manifest revision and state in the
+    /// manifest of the workspace backup it belongs to.
+    fn record_module(&self, rev: Oid, pristine: bool) -> Result<(), SyncError> {
+        let d = self.dir_unchecked();
+        let ws = match d
+            .src_path
+            .ancestors()
+            .skip(1)
+            .find(|a| a.join(".west").join("config").is_file())
+        {
+            Some(ws) => ws,
+            None => return Ok(()),
+        };
+        let rp = d.src_path.strip_prefix(ws).unwrap();
+        let m = match d.target_path.ancestors().nth(rp.components().count()) {
+            Some(t) => t.join(".west").join("manifest"),
+            None => return Ok(()),
+        };
+        // the workspace itself is not part of the backup
+        if !m.parent().unwrap().is_dir() {
+            return Ok(());
+        }
+
+        let state = if pristine { "pristine" } else { "modified" };
+        utils::manifest_add(
+            &m,
+            &["module", &rp.to_string_lossy(), &rev.to_string(), state],
+        )
+    }
+}
+
+/// Get 'path' and 'file' of section 'manifest' from '.west/config'.
+fn parse_config(s: &str) -> (Option<String>, Option<String>) {
+    let mut section = "";
+    let (mut path, mut file) = (None, None);
+    for l in s.lines().map(|l| l.trim()) {
+        if l.starts_with('[') && l.ends_with(']') {
+            section = &l[1..l.len() - 1];
+        } else if section == "manifest" {
+            if let Some((k, v)) = l.split_once('=') {
+                match k.trim() {
+                    "path" => path = Some(v.trim().to_string()),
+                    "file" => file = Some(v.trim().to_string()),
+                    _ => (),
+                }
+            }
+        }
+    }
+    (path, file)
+}
+
+/// The revision west checked out from the manifest.
+fn manifest_rev(r: &Repository) -> Option<Oid> {
+    r.find_reference("refs/heads/manifest-rev").ok()?.target()
+}
+
+/// A module is pristine if it is checked out at its manifest revision
+/// 'rev' without local changes, stashes or local commits.
+fn module_pristine(r: &Repository, rev: Oid) -> Result<bool, SyncError> {
+    if r.head()?.target() != Some(rev) || r.find_reference("refs/stash").is_ok() {
+        return Ok(false);
+    }
+
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true).include_ignored(false);
+    if !r.statuses(Some(&mut opts))?.is_empty() {
+        return Ok(false);
+    }
+
+    // local branches must be contained in the manifest revision or
+    // in a remote branch
+    let remotes: Vec<Oid> = r
+        .branches(Some(BranchType::Remote))?
+        .flatten()
+        .filter_map(|(b, _)| b.get().target())
+        .collect();
+    for (b, _) in r.branches(Some(BranchType::Local))?.flatten() {
+        if let Some(tip) = b.get().target() {
+            let contained = |o: Oid| o == tip || r.graph_descendant_of(o, tip).unwrap_or(false);
+            if !contained(rev) && !remotes.iter().any(|o| contained(*o)) {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+impl Flavour for West {
+    fn init_opts(opts: &mut getopts::Options) {
+        opts.optflag("", "west-ignore", "Ignore west workspaces");
+    }
+
+    fn template(args: &getopts::Matches) -> Self {
+        West {
+            dir: Box::new(None),
+            ignore: args.opt_present("west-ignore"),
+            kind: Kind::Unknown,
+            git: Git::template(args),
+        }
+    }
+
+    /// Look for '.west/config' to identify a west workspace or for
+    /// the branch 'manifest-rev' to identify a module, modules are
+    /// left to other types with --git-ignore or --git-full.
+    fn probe(&self, d: &Dir) -> Option<Box<dyn Flavour + Send + Sync>> {
+        for e in &d.dirs {
+            if e.file_name().unwrap() == ".west" && e.join("config").is_file() {
+                return Some(self.build());
+            }
+        }
+        if !self.git.skip()
+            && !self.git.recurse()
+            && d.dirs.iter().any(|e| e.file_name().unwrap() == ".git")
+            && Repository::open(&d.src_path)
+                .ok()
+                .and_then(|r| manifest_rev(&r))
+                .is_some()
+        {
+            return Some(self.build());
+        }
+        None
+    }
+
+    fn build(&self) -> Box<dyn Flavour + Send + Sync> {
+        Box::new(self.copy())
+    }
+
+    fn set_dir(&mut self, d: Dir) {
+        *self.dir = Some(d);
+    }
+
+    fn dir(&self) -> &Option<Dir> {
+        &self.dir
+    }
+
+    fn dir_mut(&mut self) -> &mut Option<Dir> {
+        &mut self.dir
+    }
+
+    fn category(&self) -> Category {
+        Category::Special
+    }
+
+    /// Modules are not scanned, they are backed up as Git
+    /// repositories.
+    fn recurse(&self) -> bool {
+        !self.skip() && !matches!(self.kind, Kind::Module(_))
+    }
+
+    fn skip(&self) -> bool {
+        self.ignore
+    }
+
+    /// Subdirectories of the workspace are probed.
+    fn stay(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> &'static str {
+        "West"
+    }
+
+    /// Find out what the directory is, pristine modules do not get a
+    /// backup at all. The workspace is backed up here already so that
+    /// modules can add their records to its manifest.
+    fn prepare(&mut self) -> Result<(), SyncError> {
+        let d = match self.dir.as_mut() {
+            Some(d) => d,
+            None => {
+                return Err(SyncError::Failed(
+                    "Cannot prepare synchronization without directory".to_string(),
+                ))
+            }
+        };
+
+        if d.src_path.join(".west").join("config").is_file() {
+            d.dirs.retain(|e| e.file_name().unwrap() != ".west");
+            d.ensure_target_path()?;
+            self.kind = Kind::Workspace;
+            if let Err(e) = self.dup_workspace() {
+                self.dir_unchecked().send_runtime(stats::Info {
+                    category: self.category(),
+                    name: self.name().to_string(),
+                    desc: format!("Failed to backup workspace because {}", e),
+                });
+            }
+            return Ok(());
+        }
+
+        let state = Repository::open(&d.src_path)
+            .map_err(SyncError::from)
+            .and_then(|r| match manifest_rev(&r) {
+                Some(rev) => Ok((rev, module_pristine(&r, rev)?)),
+                None => Err(SyncError::Failed("No manifest revision".to_string())),
+            });
+        let res = match state {
+            Ok((rev, pristine)) => {
+                self.kind = Kind::Module(pristine);
+                self.record_module(rev, pristine)
+            }
+            Err(e) => {
+                // back up the module as modified
+                self.kind = Kind::Module(false);
+                Err(e)
+            }
+        };
+        let d = self.dir_unchecked();
+        if let Err(e) = res {
+            d.send_runtime(stats::Info {
+                category: self.category(),
+                name: self.name().to_string(),
+                desc: format!(
+                    "Failed to record module {:?} in workspace manifest because {}",
+                    d.src_path, e
+                ),
+            });
+        }
+
+        let pristine = self.kind == Kind::Module(true);
+        let d = self.dir_mut().as_mut().unwrap();
+        if pristine {
+            trace!("Module {:?} is pristine", d.src_path);
+            if d.config.delete && d.target_path.exists() {
+                fs::remove_dir_all(&d.target_path)?;
+            }
+            Ok(())
+        } else {
+            d.ensure_target_path()
+        }
+    }
+
+    fn dup(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            match self.kind {
+                Kind::Module(true) => Ok(()),
+                Kind::Module(false) => self
+                    .git
+                    .dup_nested(d.sub_dir(d.src_path.clone(), d.target_path.clone())),
+                _ => d.dup(),
+            }
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+
+    fn merge(&self) -> Result<(), SyncError> {
+        if let Some(d) = self.dir() {
+            match self.kind {
+                Kind::Module(true) => Ok(()),
+                Kind::Module(false) => self
+                    .git
+                    .dup_nested(d.sub_dir(d.src_path.clone(), d.target_path.clone())),
+                _ => d.merge(),
+            }
+        } else {
+            Err(SyncError::Failed(
+                "Cannot synchronize without directory".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dir::git::test::{init_repo, path, write};
+    use crate::scanner::stats;
+    use crate::Config;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_prepare() {
+        let p = path("west_prepare");
+        let ws = p.join("ws");
+        write(&ws, ".west/config", "[manifest]\npath = zephyr\n");
+        let mut revs = vec![];
+        for m in ["zephyr", "modules/lib/foo"] {
+            let r = init_repo(&ws.join(m), &[("a", "a\n")]);
+            let c = r.head().unwrap().peel_to_commit().unwrap();
+            r.branch("manifest-rev", &c, false).unwrap();
+            revs.push(c.id().to_string());
+        }
+        write(&ws.join("modules/lib/foo"), "untracked", "u\n");
+
+        let cfg = Arc::new(Config {
+            jobs: 1,
+            delete: true,
+            archive: false,
+            owned: false,
+            ignore: vec![],
+        });
+        let stats = stats::Stats::default();
+        let mut opts = getopts::Options::new();
+        Git::init_opts(&mut opts);
+        West::init_opts(&mut opts);
+        let west = West::template(&opts.parse(&[] as &[&str]).unwrap());
+        // pristine modules get no backup
+        for (m, backup) in [("", true), ("zephyr", false), ("modules/lib/foo", true)] {
+            let mut d = Dir::new(0, cfg.clone(), stats.sender().clone())
+                .set_src_path(ws.join(m))
+                .set_target_path(p.join("backup").join(m));
+            utils::save_dirs_and_files(&ws.join(m), &mut d.dirs, &mut d.files, None, false)
+                .unwrap();
+            let mut f = west.probe(&d).expect("Not claimed");
+            f.set_dir(d);
+            f.prepare().unwrap();
+            assert_eq!(p.join("backup").join(m).exists(), backup, "{}", m);
+        }
+
+        let m = utils::manifest_read(&p.join("backup/.west/manifest")).unwrap();
+        assert_eq!(
+            m,
+            [
+                vec!["manifest", "zephyr", "west.yml", &revs[0]],
+                vec!["module", "zephyr", &revs[0], "pristine"],
+                vec!["module", "modules/lib/foo", &revs[1], "modified"],
+            ]
+        );
+        assert!(stats.chn.1.try_iter().next().is_none());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let c = "[manifest]\npath = zephyr\nfile = west.yml\n\n[zephyr]\nbase = zephyr\n";
+        assert_eq!(
+            parse_config(c),
+            (Some("zephyr".to_string()), Some("west.yml".to_string()))
+        );
+        let c = "[zephyr]\npath = x\n[manifest]\npath=nrf\n";
+        assert_eq!(parse_config(c), (Some("nrf".to_string()), None));
+    }
+}
@@ -91,7 +91,7 @@ impl Flavour for Yocto {
             });
         }
 
-        self.dir = Box::new(Some(d));
+        *self.dir = Some(d);
     }
 
     fn dir(&self) -> &Option<Dir> {
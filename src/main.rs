@@ -16,6 +16,8 @@ use simple_logger::SimpleLogger;
 
 mod dir;
 use crate::dir::Flavour;
+mod restore;
+use restore::Restore;
 mod scanner;
 use scanner::{stats, Scanner};
 mod ui;
@@ -42,13 +44,12 @@ pub struct Config {
 }
 
 /// Prints help page.
-fn usage(program: &str, opts: getopts::Options, err: Option<getopts::Fail>) {
-    if err.is_some() {
-        let msg = format!("Error: {}", err.unwrap());
+fn usage(brief: &str, opts: getopts::Options, err: Option<getopts::Fail>) {
+    if let Some(e) = err {
+        let msg = format!("Error: {}", e);
         println!("{}\n", msg)
     }
-    let brief = format!("Usage: {} [options]", program);
-    print!("{}", opts.usage(&brief));
+    print!("{}", opts.usage(brief));
 }
 
 /// Read command line arguments from file.
@@ -69,10 +70,56 @@ fn write_args_to_file(args: &[String], p: &Path) -> Result<(), std::io::Error> {
     Ok(())
 }
 
+/// Restore mode, rebuilds working copies from a backup.
+fn restore(program: &str, raw_args: &[String]) {
+    let brief = format!("Usage: {} restore [options]", program);
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "Usage hints");
+    opts.optopt("s", "source", "Backup directory to restore from", "DIR");
+    opts.optopt("t", "target", "Target directory to restore into", "DIR");
+
+    let args = match opts.parse(raw_args) {
+        Ok(m) => m,
+        Err(e) => {
+            usage(&brief, opts, Some(e));
+            return;
+        }
+    };
+
+    if args.opt_present("help") {
+        usage(&brief, opts, None);
+        return;
+    }
+
+    if !args.opt_present("s") || !args.opt_present("t") {
+        error!("Missing source or target path");
+        usage(&brief, opts, None);
+        return;
+    }
+
+    let src = match Path::new(&args.opt_str("s").unwrap()).canonicalize() {
+        Ok(p) => p,
+        Err(_) => panic!("Invalid source path"),
+    };
+
+    let t = &args.opt_str("t").unwrap();
+    fs::create_dir_all(t).expect("Cannot create target path");
+    let target = Path::new(t).canonicalize().unwrap();
+
+    for r in Restore::new(&src, &target).run() {
+        print!("{}", r);
+    }
+}
+
 /// Entry point.
 fn main() {
     let mut raw_args: Vec<String> = std::env::args().collect();
     let program = raw_args[0].clone();
+    let brief = format!(
+        "Usage: {0} [options]\n       {0} restore [options]",
+        program
+    );
     let mut opts = getopts::Options::new();
     let mut session_file = false;
 
@@ -83,6 +130,11 @@ fn main() {
         .init()
         .unwrap();
 
+    if raw_args.get(1).map(String::as_str) == Some("restore") {
+        restore(&program, &raw_args[2..]);
+        return;
+    }
+
     opts.optflag("h", "help", "Usage hints");
     opts.optopt("s", "source", "Source directory", "DIR");
     opts.optopt("t", "target", "Target directory", "DIR");
@@ -101,13 +153,26 @@ fn main() {
     // we have to get the flavour specific options
     dir::Yocto::init_opts(&mut opts);
     dir::Sysroot::init_opts(&mut opts);
+    dir::Buildroot::init_opts(&mut opts);
+    dir::OpenWrt::init_opts(&mut opts);
+    dir::Kernel::init_opts(&mut opts);
+    dir::Repo::init_opts(&mut opts);
+    dir::West::init_opts(&mut opts);
+    dir::Autotools::init_opts(&mut opts);
+    dir::Bazel::init_opts(&mut opts);
     dir::Cmake::init_opts(&mut opts);
     dir::Flutter::init_opts(&mut opts);
     dir::Meson::init_opts(&mut opts);
     dir::Ninja::init_opts(&mut opts);
+    dir::Jvm::init_opts(&mut opts);
+    dir::LangCache::init_opts(&mut opts);
+    dir::Node::init_opts(&mut opts);
+    dir::Python::init_opts(&mut opts);
     dir::Cargo::init_opts(&mut opts);
+    dir::Jj::init_opts(&mut opts);
     dir::Git::init_opts(&mut opts);
     dir::Svn::init_opts(&mut opts);
+    dir::Hg::init_opts(&mut opts);
     dir::Simple::init_opts(&mut opts);
 
     // if we do not have sufficient arguments try to get them from a
@@ -120,7 +185,7 @@ fn main() {
                 trace!("Using command line arguments from session file");
             }
             Err(_) => {
-                usage(&program, opts, None);
+                usage(&brief, opts, None);
                 return;
             }
         }
@@ -130,23 +195,31 @@ fn main() {
     let args = match opts.parse(&raw_args[1..]) {
         Ok(m) => m,
         Err(e) => {
-            usage(&program, opts, Some(e));
+            usage(&brief, opts, Some(e));
             return;
         }
     };
 
     if args.opt_present("help") {
-        usage(&program, opts, None);
+        usage(&brief, opts, None);
         return;
     }
 
     // these are required, but optional because of 'h'
     if !args.opt_present("s") || !args.opt_present("t") {
         error!("Missing source or target path");
-        usage(&program, opts, None);
+        usage(&brief, opts, None);
         return;
     }
 
+    if let Some(s) = args.opt_str("git-untracked-max-size") {
+        if utils::parse_size(&s).is_none() {
+            error!("Invalid size {} for --git-untracked-max-size", s);
+            usage(&brief, opts, None);
+            return;
+        }
+    }
+
     // prepare our scanner
     let src = match Path::new(&args.opt_str("s").unwrap()).canonicalize() {
         Ok(p) => p,
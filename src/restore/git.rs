@@ -0,0 +1,702 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use git2::build::CheckoutBuilder;
+use git2::{
+    ApplyLocation, AutotagOption, BranchType, Config, ConfigLevel, Diff, FetchOptions, Oid,
+    Repository, Signature,
+};
+use log::trace;
+
+use super::utils::SyncError;
+use super::{utils, Report};
+
+/// Prefix for references fetched from a backup repository.
+const FETCH_PREFIX: &str = "refs/devsync/";
+
+/// Check if section exists in backup, either as directory or as
+/// '.empty' or '.ignored' marker.
+fn has_section(p: &Path, n: &str) -> bool {
+    p.join(n).is_dir()
+        || p.join(format!("{}.empty", n)).exists()
+        || p.join(format!("{}.ignored", n)).exists()
+}
+
+/// Check if path holds a backup of the Git flavour.
+pub fn is_backup(p: &Path) -> bool {
+    (has_section(p, "repo") || p.join("repo.bundle").is_file()) && has_section(p, "stashes")
+}
+
+/// Restore Git backup from 'b' into the new working copy 't'.
+pub fn restore(b: &Path, t: &Path, rep: &mut Report) -> Result<(), SyncError> {
+    if t.exists() && fs::read_dir(t)?.next().is_some() {
+        return Err(SyncError::Failed(format!(
+            "Restore target {:?} is not empty",
+            t
+        )));
+    }
+    fs::create_dir_all(t)?;
+
+    let m = utils::manifest_read(&b.join("manifest")).unwrap_or_default();
+    let head = m
+        .iter()
+        .find(|e| e.len() == 2 && e[0] == "head")
+        .map(|e| e[1].as_str());
+    let detached = m
+        .iter()
+        .find(|e| e.len() == 2 && e[0] == "detached")
+        .and_then(|e| Oid::from_str(&e[1]).ok());
+
+    let mut r = Repository::init(t)?;
+    for e in m.iter().filter(|e| e.len() == 3 && e[0] == "remote") {
+        trace!("Add remote {} with url {}", e[1], e[2]);
+        r.remote(&e[1], &e[2])?;
+    }
+
+    let mp = b.join("meta");
+    if mp.is_dir() {
+        match restore_meta(&r, &mp) {
+            Ok(n) => rep.restored.push(format!("{} meta file(s)", n)),
+            Err(e) => rep.failed.push(format!("meta data because {}", e)),
+        }
+    }
+
+    let bp = b.join("repo");
+    let bundle = b.join("repo.bundle");
+    if bp.is_dir() {
+        for n in fetch_backup(&r, &bp)? {
+            rep.restored
+                .push(format!("branch {} from backup repository", n));
+        }
+    } else {
+        match fetch_upstream(&r, head)? {
+            Some(u) => rep.restored.push(format!("clone from upstream {}", u)),
+            None if bundle.is_file() => (),
+            None => {
+                return Err(SyncError::Failed(
+                    "Neither backup repository nor upstream remote".to_string(),
+                ))
+            }
+        }
+        if bundle.is_file() {
+            for n in apply_bundle(&r, &bundle)? {
+                match n.strip_prefix("refs/heads/") {
+                    Some(b) => rep.restored.push(format!("branch {} from bundle", b)),
+                    None => rep.restored.push(format!("{} from bundle", n)),
+                }
+            }
+        }
+    }
+
+    // LFS objects first, checkout leaves pointer files in the
+    // working copy until 'git lfs checkout' runs
+    let lp = b.join("lfs");
+    if lp.is_dir() {
+        match overlay(&lp, &r.path().join("lfs").join("objects")) {
+            Ok(n) => rep.restored.push(format!("{} LFS object(s)", n)),
+            Err(e) => rep.failed.push(format!("LFS objects because {}", e)),
+        }
+    }
+
+    match detached {
+        Some(id) => {
+            checkout_detached(&r, id)?;
+            rep.restored.push(format!("detached HEAD at {}", id));
+        }
+        None => checkout(&r, head)?,
+    }
+
+    for e in m.iter().filter(|e| e.len() == 4 && e[0] == "upstream") {
+        if r.find_branch(&e[1], BranchType::Local).is_err() {
+            continue;
+        }
+        match set_upstream(&r, &e[1], &e[2], &e[3]) {
+            Ok(_) => rep
+                .restored
+                .push(format!("upstream {} {} of {}", e[2], e[3], e[1])),
+            Err(err) => rep
+                .failed
+                .push(format!("upstream of {} because {}", e[1], err)),
+        }
+    }
+
+    restore_stashes(&mut r, &b.join("stashes"), rep);
+
+    let sp = b.join("staged");
+    if sp.is_dir() {
+        match restore_staged(&r, &sp, t, &m) {
+            Ok(n) => rep.restored.push(format!("{} staged file(s)", n)),
+            Err(e) => rep.failed.push(format!("staged files because {}", e)),
+        }
+    }
+
+    match restore_typechanges(&m, "unstaged", t) {
+        Ok(n) if n.is_empty() => (),
+        Ok(n) => rep.restored.push(format!("{} type change(s)", n.len())),
+        Err(e) => rep.failed.push(format!("type changes because {}", e)),
+    }
+
+    for s in ["unstaged", "untracked", "ignored"] {
+        let sp = b.join(s);
+        if !sp.is_dir() {
+            continue;
+        }
+        match overlay(&sp, t) {
+            Ok(n) => rep.restored.push(format!("{} {} file(s)", n, s)),
+            Err(e) => rep.failed.push(format!("{} files because {}", s, e)),
+        }
+    }
+
+    for f in removed(&m, "unstaged") {
+        match fs::remove_file(t.join(f)) {
+            Ok(_) => rep.restored.push(format!("removal of {}", f)),
+            Err(e) => rep.failed.push(format!("removal of {} because {}", f, e)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Settings of 'core' that describe the layout of the original
+/// repository and are left to the new one.
+const CORE_LAYOUT: [&str; 3] = ["core.bare", "core.worktree", "core.repositoryformatversion"];
+
+/// Copy hooks, excludes and sparse-checkout patterns from 'meta'
+/// into the git directory and apply the repository configuration
+/// except for [CORE_LAYOUT] and 'extensions', which require a
+/// matching repository format. Remotes and branch upstreams are
+/// restored from the manifest. Returns the number of restored files.
+fn restore_meta(r: &Repository, p: &Path) -> Result<usize, SyncError> {
+    let mut files = Vec::new();
+    list_files(p, &mut files)?;
+    files.retain(|f| f.strip_prefix(p).is_ok_and(|f| f != Path::new("config")));
+    for f in &files {
+        utils::cp_d(p, r.path(), f, true)?;
+    }
+
+    let cp = p.join("config");
+    if cp.is_file() {
+        let mut cfg = r.config()?.open_level(ConfigLevel::Local)?;
+        let bcfg = Config::open(&cp)?;
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut entries = bcfg.entries(None)?;
+        while let Some(e) = entries.next() {
+            let e = e?;
+            let (n, v) = match (e.name(), e.value()) {
+                (Some(n), Some(v)) => (n.to_string(), v.to_string()),
+                _ => continue,
+            };
+            if CORE_LAYOUT.contains(&n.as_str())
+                || n.starts_with("extensions.")
+                || n.starts_with("branch.")
+                || (n.starts_with("remote.") && (n.ends_with(".url") || n.ends_with(".fetch")))
+            {
+                continue;
+            }
+
+            trace!("Set config {} to {}", n, v);
+            if seen.insert(n.clone()) {
+                cfg.set_str(&n, &v)?;
+            } else {
+                // add another value for multivars
+                cfg.set_multivar(&n, "^$", &v)?;
+            }
+        }
+        files.push(cp);
+    }
+
+    Ok(files.len())
+}
+
+/// Configure upstream remote 'rn' and merge reference 'mr' of
+/// branch 'b'.
+fn set_upstream(r: &Repository, b: &str, rn: &str, mr: &str) -> Result<(), SyncError> {
+    let mut cfg = r.config()?.open_level(ConfigLevel::Local)?;
+    cfg.set_str(&format!("branch.{}.remote", b), rn)?;
+    cfg.set_str(&format!("branch.{}.merge", b), mr)?;
+    Ok(())
+}
+
+/// Fetch all branches, tags, notes and a detached HEAD from the bare
+/// backup repository and return the names of the created local
+/// branches.
+fn fetch_backup(r: &Repository, p: &Path) -> Result<Vec<String>, SyncError> {
+    let mut remote = r.remote_anonymous(p.to_str().unwrap())?;
+    remote.fetch(
+        &[
+            &format!("+refs/heads/*:{}heads/*", FETCH_PREFIX),
+            &format!("+refs/remotes/origin/*:{}origin/*", FETCH_PREFIX),
+            "+refs/tags/*:refs/tags/*",
+            "+refs/notes/*:refs/notes/*",
+            &format!("+{0}HEAD:{0}HEAD", FETCH_PREFIX),
+        ],
+        Some(FetchOptions::new().download_tags(AutotagOption::All)),
+        None,
+    )?;
+
+    // the bare clone keeps the local branches of the original
+    // repository as remote tracking branches, only the checked out
+    // branch is a local branch
+    let mut refs: Vec<(String, Oid)> = Vec::new();
+    for pfx in ["origin/", "heads/"] {
+        let g = format!("{}{}*", FETCH_PREFIX, pfx);
+        for rf in r.references_glob(&g)?.flatten() {
+            let n = rf.name().unwrap().to_string();
+            if let Some(id) = rf.target() {
+                refs.push((n, id));
+            }
+        }
+    }
+
+    let mut branches = Vec::new();
+    for (n, id) in &refs {
+        let b = n
+            .trim_start_matches(FETCH_PREFIX)
+            .split_once('/')
+            .unwrap()
+            .1;
+        if b != "HEAD" && r.find_reference(&format!("refs/heads/{}", b)).is_err() {
+            r.reference(&format!("refs/heads/{}", b), *id, false, "devsync restore")?;
+            branches.push(b.to_string());
+        }
+        r.find_reference(n)?.delete()?;
+    }
+
+    Ok(branches)
+}
+
+/// Fetch from upstream remote and create the local branch 'head' if
+/// upstream has it, returns the url of the upstream remote or None if
+/// there is no remote.
+fn fetch_upstream(r: &Repository, head: Option<&str>) -> Result<Option<String>, SyncError> {
+    let remotes = r.remotes()?;
+    let n = match remotes.iter().flatten().find(|n| *n == "origin") {
+        Some(n) => n,
+        None => match remotes.iter().flatten().next() {
+            Some(n) => n,
+            None => return Ok(None),
+        },
+    };
+
+    let mut remote = r.find_remote(n)?;
+    remote.fetch(&[] as &[&str], None, None)?;
+
+    if let Some(h) = head {
+        if let Ok(rb) = r.find_reference(&format!("refs/remotes/{}/{}", n, h)) {
+            let c = rb.peel_to_commit()?;
+            let mut b = r.branch(h, &c, false)?;
+            b.set_upstream(Some(&format!("{}/{}", n, h)))?;
+        }
+    }
+
+    Ok(Some(remote.url().unwrap_or_default().to_string()))
+}
+
+/// Add the pack from bundle to the repository and create the
+/// references, returns the names of the references. A detached HEAD
+/// is only added as object, see [checkout_detached].
+fn apply_bundle(r: &Repository, p: &Path) -> Result<Vec<String>, SyncError> {
+    let b = fs::read(p)?;
+    let mut refs: Vec<(String, Oid)> = Vec::new();
+    let mut pos = 0;
+    for l in b.split(|c| *c == b'\n') {
+        pos += l.len() + 1;
+        let l = String::from_utf8_lossy(l);
+        if l.is_empty() {
+            break;
+        } else if l.starts_with('#') {
+            continue;
+        }
+
+        let (id, n) = l.split_once(' ').unwrap_or((&l, ""));
+        if let Some(id) = id.strip_prefix('-') {
+            if r.find_commit(Oid::from_str(id)?).is_err() {
+                return Err(SyncError::Failed(format!(
+                    "Bundle requires commit {} which upstream does not have",
+                    id
+                )));
+            }
+        } else {
+            refs.push((n.to_string(), Oid::from_str(id)?));
+        }
+    }
+
+    let odb = r.odb()?;
+    let mut w = odb.packwriter()?;
+    w.write_all(&b[pos..])?;
+    w.commit()?;
+
+    let mut names = Vec::new();
+    for (n, id) in refs.into_iter().filter(|(n, _)| n != "HEAD") {
+        trace!("Create {} from bundle", n);
+        r.reference(&n, id, true, "devsync restore")?;
+        names.push(n);
+    }
+
+    Ok(names)
+}
+
+/// Check out branch 'head' or the first local branch if that does
+/// not exist.
+fn checkout(r: &Repository, head: Option<&str>) -> Result<(), SyncError> {
+    let h = match head.filter(|h| r.find_reference(&format!("refs/heads/{}", h)).is_ok()) {
+        Some(h) => format!("refs/heads/{}", h),
+        None => match r.references_glob("refs/heads/*")?.flatten().next() {
+            Some(rf) => rf.name().unwrap().to_string(),
+            None => {
+                return Err(SyncError::Failed(
+                    "No branch available for checkout".to_string(),
+                ))
+            }
+        },
+    };
+
+    trace!("Check out {}", h);
+    r.set_head(&h)?;
+    r.checkout_head(Some(CheckoutBuilder::new().force()))?;
+    Ok(())
+}
+
+/// Check out commit 'id' as detached HEAD.
+fn checkout_detached(r: &Repository, id: Oid) -> Result<(), SyncError> {
+    trace!("Check out detached {}", id);
+    r.set_head_detached(id)?;
+    r.checkout_head(Some(CheckoutBuilder::new().force()))?;
+    if let Ok(mut rf) = r.find_reference(&format!("{}HEAD", FETCH_PREFIX)) {
+        rf.delete()?;
+    }
+    Ok(())
+}
+
+/// Get stash message from mbox subject.
+fn stash_message(b: &[u8]) -> Option<String> {
+    let s = String::from_utf8_lossy(b);
+    let mut l = s.lines().skip_while(|l| !l.starts_with("Subject: "));
+    let mut subject = l.next()?.trim_start_matches("Subject: ").to_string();
+    // subject lines may be folded
+    for c in l.take_while(|l| l.starts_with(' ') || l.starts_with('\t')) {
+        subject.push_str(c);
+    }
+
+    // let git generate the default message again
+    let m = subject.trim_start_matches("[PATCH] ");
+    if m.starts_with("WIP on ") {
+        None
+    } else if let Some((_, m)) = m.strip_prefix("On ").and_then(|m| m.split_once(": ")) {
+        Some(m.to_string())
+    } else {
+        Some(m.to_string())
+    }
+}
+
+/// Apply mbox patch of stash to working copy and stash the result.
+fn restore_stash(r: &mut Repository, f: &Path) -> Result<(), SyncError> {
+    let b = fs::read(f)?;
+    let d = Diff::from_buffer(&b)?;
+    r.apply(&d, ApplyLocation::WorkDir, None)?;
+    let sig = r
+        .signature()
+        .or_else(|_| Signature::now("devsync", "devsync@localhost"))?;
+    r.stash_save2(&sig, stash_message(&b).as_deref(), None)?;
+    Ok(())
+}
+
+/// Restore stashes, oldest stash first to keep the order.
+fn restore_stashes(r: &mut Repository, p: &Path, rep: &mut Report) {
+    let mut stashes: Vec<(usize, PathBuf)> = match fs::read_dir(p) {
+        Ok(e) => e
+            .flatten()
+            .filter_map(|e| {
+                let n = e.file_name().to_str()?.to_string();
+                let i = n.split_once('-')?.0.parse::<usize>().ok()?;
+                Some((i, e.path()))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    stashes.sort_by_key(|s| std::cmp::Reverse(s.0));
+
+    for (i, f) in stashes {
+        match restore_stash(r, &f) {
+            Ok(_) => rep.restored.push(format!("stash {}", i)),
+            Err(e) => rep.failed.push(format!("stash {} because {}", i, e)),
+        }
+    }
+}
+
+/// Restore the index from staged patch or staged files and return
+/// the number of staged files.
+fn restore_staged(
+    r: &Repository,
+    s: &Path,
+    t: &Path,
+    m: &[Vec<String>],
+) -> Result<usize, SyncError> {
+    let p = s.join("index.patch");
+    if p.is_file() {
+        let d = Diff::from_buffer(&fs::read(p)?)?;
+        r.apply(&d, ApplyLocation::Both, None)?;
+        return Ok(d.deltas().len());
+    }
+
+    let links = restore_typechanges(m, "staged", t)?;
+    let n = overlay(s, t)?;
+    let mut files = Vec::new();
+    list_files(s, &mut files)?;
+    let mut i = r.index()?;
+    for f in &files {
+        i.add_path(f.strip_prefix(s).unwrap())?;
+    }
+    for f in links.iter().filter(|f| t.join(f).is_symlink()) {
+        i.add_path(Path::new(f))?;
+    }
+    for f in removed(m, "staged") {
+        i.remove_path(Path::new(f))?;
+        let _ = fs::remove_file(t.join(f));
+    }
+    i.write()?;
+
+    Ok(n + links.len())
+}
+
+/// Get files recorded in manifest as deleted or renamed for section
+/// 'n'.
+fn removed<'a>(m: &'a [Vec<String>], n: &'a str) -> impl Iterator<Item = &'a str> {
+    m.iter()
+        .filter(move |e| e.len() >= 3 && e[1] == n)
+        .filter(|e| e[0] == "deleted" || e[0] == "renamed")
+        .map(|e| e[2].as_str())
+}
+
+/// Remove the files recorded in manifest as type changed for section
+/// 'n' and create the recorded symbolic links, new regular files are
+/// copied from the section afterwards. Returns the type changed
+/// files.
+fn restore_typechanges<'a>(
+    m: &'a [Vec<String>],
+    n: &str,
+    t: &Path,
+) -> Result<Vec<&'a str>, SyncError> {
+    let mut files = Vec::new();
+    for e in m
+        .iter()
+        .filter(|e| e.len() >= 3 && e[0] == "typechange" && e[1] == n)
+    {
+        let p = t.join(&e[2]);
+        match fs::symlink_metadata(&p) {
+            Ok(md) if md.is_dir() => fs::remove_dir_all(&p)?,
+            Ok(_) => fs::remove_file(&p)?,
+            Err(_) => (),
+        }
+        if let Some(l) = e.get(3) {
+            trace!("Create link {:?} to {}", p, l);
+            fs::create_dir_all(p.parent().unwrap())?;
+            std::os::unix::fs::symlink(l, &p)?;
+        }
+        files.push(e[2].as_str());
+    }
+
+    Ok(files)
+}
+
+/// Collect all files from path recursively.
+fn list_files(p: &Path, files: &mut Vec<PathBuf>) -> Result<(), SyncError> {
+    let mut dirs = Vec::new();
+    utils::save_dirs_and_files(p, &mut dirs, files, None, false)?;
+    for d in &dirs {
+        list_files(d, files)?;
+    }
+    Ok(())
+}
+
+/// Collect all symbolic links from path recursively.
+fn list_links(p: &Path, links: &mut Vec<PathBuf>) -> Result<(), SyncError> {
+    for e in fs::read_dir(p)?.flatten() {
+        let ft = e.file_type()?;
+        if ft.is_symlink() {
+            links.push(e.path());
+        } else if ft.is_dir() {
+            list_links(&e.path(), links)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy all files and symbolic links from backup section into the
+/// working copy and return the number of copied entries.
+fn overlay(s: &Path, t: &Path) -> Result<usize, SyncError> {
+    let mut files = Vec::new();
+    list_files(s, &mut files)?;
+    for f in &files {
+        utils::cp_d(s, t, f, true)?;
+    }
+
+    let mut links = Vec::new();
+    list_links(s, &mut links)?;
+    for l in &links {
+        utils::ln_r_d(s, t, l.strip_prefix(s).unwrap())?;
+    }
+
+    Ok(files.len() + links.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dir::git::test::{backup, clone_repo, commit, init_repo, path, stage, write};
+    use git2::{DiffFormat, StashFlags, Status};
+
+    /// Branch, HEAD, status, staged changes and stashes of repository.
+    #[derive(Debug, PartialEq)]
+    struct State {
+        branch: String,
+        head: Oid,
+        status: Vec<(String, Status)>,
+        staged: String,
+        stashes: Vec<String>,
+    }
+
+    fn state(r: &mut Repository) -> State {
+        let (branch, head) = {
+            let h = r.head().unwrap();
+            (h.shorthand().unwrap().to_string(), h.target().unwrap())
+        };
+
+        let mut status: Vec<(String, Status)> = r
+            .statuses(Some(git2::StatusOptions::new().include_untracked(true)))
+            .unwrap()
+            .iter()
+            .map(|s| (s.path().unwrap().to_string(), s.status()))
+            .collect();
+        status.sort();
+
+        let tree = r.find_commit(head).unwrap().tree().unwrap();
+        let mut staged = String::new();
+        r.diff_tree_to_index(Some(&tree), None, None)
+            .unwrap()
+            .print(DiffFormat::Patch, |_, _, l| {
+                staged.push_str(&String::from_utf8_lossy(l.content()));
+                true
+            })
+            .unwrap();
+        drop(tree);
+
+        let mut stashes = Vec::new();
+        r.stash_foreach(|_, m, _| {
+            stashes.push(m.to_string());
+            true
+        })
+        .unwrap();
+
+        State {
+            branch,
+            head,
+            status,
+            staged,
+            stashes,
+        }
+    }
+
+    /// Clone of upstream with an unpushed commit, a stash and staged,
+    /// unstaged and untracked files.
+    fn sample_repo(u: &Path, p: &Path) -> Repository {
+        init_repo(u, &[("a", "a\n"), ("b", "b\n"), ("dir/c", "c\n")]);
+        let mut r = clone_repo(u, p);
+        commit(&r, &[("a", "a\nlocal\n")], "local");
+
+        write(p, "b", "b\nstashed\n");
+        let sig = Signature::now("devsync", "devsync@localhost").unwrap();
+        r.stash_save2(&sig, Some("keep b"), Some(StashFlags::DEFAULT))
+            .unwrap();
+
+        stage(&r, &[("dir/c", "c\nstaged\n"), ("new", "new\n")]);
+        write(p, "b", "b\nunstaged\n");
+        write(p, "untracked/d", "d\n");
+        std::os::unix::fs::symlink("d", p.join("untracked").join("l")).unwrap();
+        r
+    }
+
+    /// Backup 'p' with 'args', restore the backup and compare the
+    /// state of both repositories.
+    fn round_trip(n: &str, args: &[&str]) {
+        let p = path(n);
+        let _ = fs::remove_dir_all(&p);
+        let mut r = sample_repo(&p.join("upstream"), &p.join("src"));
+
+        backup(&p.join("src"), &p.join("backup"), args);
+        let mut rep = Report::new("Git", &p.join("backup"), &p.join("restore"));
+        restore(&p.join("backup"), &p.join("restore"), &mut rep).unwrap();
+        assert!(rep.failed.is_empty(), "{:?}", rep.failed);
+
+        let s = state(&mut r);
+        assert_eq!(s.stashes.len(), 1);
+        assert_eq!(s.status.len(), 4);
+        let mut rr = Repository::open(p.join("restore")).unwrap();
+        assert_eq!(state(&mut rr), s);
+        for f in ["b", "untracked/d"] {
+            assert_eq!(
+                fs::read(p.join("restore").join(f)).unwrap(),
+                fs::read(p.join("src").join(f)).unwrap()
+            );
+        }
+        assert_eq!(
+            fs::read_link(p.join("restore").join("untracked/l")).unwrap(),
+            Path::new("d")
+        );
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_restore_meta() {
+        let p = path("restore_meta");
+        let _ = fs::remove_dir_all(&p);
+        let r = sample_repo(&p.join("upstream"), &p.join("src"));
+        let mut cfg = r.config().unwrap().open_level(ConfigLevel::Local).unwrap();
+        cfg.set_str("devsync.test", "value").unwrap();
+        cfg.set_str("extensions.devsync", "true").unwrap();
+        write(r.path(), "hooks/pre-commit", "#!/bin/sh\n");
+
+        backup(&p.join("src"), &p.join("backup"), &[]);
+        let mut rep = Report::new("Git", &p.join("backup"), &p.join("restore"));
+        restore(&p.join("backup"), &p.join("restore"), &mut rep).unwrap();
+        assert!(rep.failed.is_empty(), "{:?}", rep.failed);
+
+        let rr = Repository::open(p.join("restore")).unwrap();
+        let cfg = rr.config().unwrap().open_level(ConfigLevel::Local).unwrap();
+        assert_eq!(cfg.get_string("devsync.test").unwrap(), "value");
+        assert!(cfg.get_entry("extensions.devsync").is_err());
+        assert!(!cfg.get_bool("core.bare").unwrap());
+        assert!(rr.path().join("hooks/pre-commit").is_file());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_restore_bundle() {
+        round_trip("restore_bundle", &["--git-bundle"]);
+    }
+
+    #[test]
+    fn test_restore_staged_patch() {
+        round_trip("restore_staged_patch", &["--git-staged-patch"]);
+    }
+
+    #[test]
+    fn test_stash_message() {
+        let m = b"From 0 Mon Sep 17 00:00:00 2001\nSubject: [PATCH] WIP on main: 9e099be init\n\n";
+        assert!(stash_message(m).is_none());
+        let m = b"Subject: [PATCH] On main: some\n longer message\n\n---\n";
+        assert!(stash_message(m).unwrap() == "some longer message");
+        let m = b"Subject: [PATCH] custom\n\n---\n";
+        assert!(stash_message(m).unwrap() == "custom");
+    }
+}
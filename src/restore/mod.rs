@@ -0,0 +1,132 @@
+// Copyright (C) 2022 Jochen Henneberg <jh@henneberg-systemdesign.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, trace};
+
+use super::utils;
+use super::{ARGS_FILE, LOG_FILE};
+
+mod git;
+
+/// Report for a single restored backup.
+#[derive(Debug)]
+pub struct Report {
+    /// Flavour name of the restored backup.
+    pub name: &'static str,
+    /// Backup path the data has been restored from.
+    pub src_path: PathBuf,
+    /// Path the backup has been restored to.
+    pub target_path: PathBuf,
+    /// Restored items.
+    pub restored: Vec<String>,
+    /// Items that could not be restored.
+    pub failed: Vec<String>,
+}
+
+impl Report {
+    /// Create new empty report.
+    fn new(name: &'static str, src: &Path, target: &Path) -> Self {
+        Report {
+            name,
+            src_path: src.to_path_buf(),
+            target_path: target.to_path_buf(),
+            restored: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} {:?} restored from {:?}",
+            self.name, self.target_path, self.src_path
+        )?;
+        for r in &self.restored {
+            writeln!(f, "  restored: {}", r)?;
+        }
+        for r in &self.failed {
+            writeln!(f, "  failed: {}", r)?;
+        }
+        Ok(())
+    }
+}
+
+/// Restore job, walks through a backup and rebuilds every backup
+/// layout it recognizes.
+pub struct Restore {
+    /// The backup path to restore from.
+    pub src_path: PathBuf,
+    /// The path to restore into.
+    pub target_path: PathBuf,
+}
+
+impl Restore {
+    /// Create new restore object.
+    pub fn new(src: &Path, target: &Path) -> Self {
+        Self {
+            src_path: src.to_path_buf(),
+            target_path: target.to_path_buf(),
+        }
+    }
+
+    /// Run the restore and return one report per restored backup.
+    pub fn run(&self) -> Vec<Report> {
+        info!(
+            "Restore contents from {:?} into {:?}",
+            self.src_path, self.target_path
+        );
+        let mut reports = Vec::new();
+        self.walk(
+            self.src_path.as_path(),
+            self.target_path.as_path(),
+            &mut reports,
+        );
+        reports
+    }
+
+    /// Look for backup layouts in path 'p' and descend into
+    /// sub-directories for all other directories, 't' is the path
+    /// 'p' is restored to.
+    fn walk(&self, p: &Path, t: &Path, reports: &mut Vec<Report>) {
+        if git::is_backup(p) {
+            trace!("Found Git backup in {:?}", p);
+            let mut r = Report::new("Git", p, t);
+            let ok = match git::restore(p, t, &mut r) {
+                Ok(_) => true,
+                Err(e) => {
+                    r.failed.push(format!("{}", e));
+                    false
+                }
+            };
+            reports.push(r);
+            // submodules are restored into the worktree
+            let sp = p.join("submodules");
+            if ok && sp.is_dir() {
+                self.walk(sp.as_path(), t, reports);
+            }
+            return;
+        }
+
+        let entries = match fs::read_dir(p) {
+            Ok(e) => e,
+            Err(_) => return,
+        };
+        for e in entries.flatten() {
+            if e.file_name() == ARGS_FILE || e.file_name() == LOG_FILE {
+                continue;
+            }
+            if let Ok(ft) = e.file_type() {
+                if ft.is_dir() {
+                    self.walk(e.path().as_path(), &t.join(e.file_name()), reports);
+                }
+            }
+        }
+    }
+}
@@ -42,13 +42,26 @@ impl Scanner {
                 Scan::new(src, target, stats, cfg)
                     .register(Box::new(dir::Yocto::template(args)))
                     .register(Box::new(dir::Sysroot::template(args)))
+                    .register(Box::new(dir::Buildroot::template(args)))
+                    .register(Box::new(dir::OpenWrt::template(args)))
+                    .register(Box::new(dir::Kernel::template(args)))
+                    .register(Box::new(dir::Repo::template(args)))
+                    .register(Box::new(dir::West::template(args)))
+                    .register(Box::new(dir::Autotools::template(args)))
+                    .register(Box::new(dir::Bazel::template(args)))
                     .register(Box::new(dir::Cmake::template(args)))
                     .register(Box::new(dir::Flutter::template(args)))
                     .register(Box::new(dir::Meson::template(args)))
                     .register(Box::new(dir::Ninja::template(args)))
+                    .register(Box::new(dir::Jvm::template(args)))
+                    .register(Box::new(dir::LangCache::template(args)))
+                    .register(Box::new(dir::Node::template(args)))
+                    .register(Box::new(dir::Python::template(args)))
                     .register(Box::new(dir::Cargo::template(args)))
+                    .register(Box::new(dir::Jj::template(args)))
                     .register(Box::new(dir::Git::template(args)))
                     .register(Box::new(dir::Svn::template(args)))
+                    .register(Box::new(dir::Hg::template(args)))
                     .register(Box::new(dir::Simple::template(args))),
             ),
         }
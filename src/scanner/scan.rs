@@ -56,8 +56,9 @@ impl Scan {
     pub fn register(mut self, c: Box<dyn dir::Flavour + Send + Sync>) -> Self {
         self.flavours.push(c);
 
-        // ensure correct order of flavours
-        self.flavours.sort_unstable_by_key(|k| k.category());
+        // ensure correct order of flavours, flavours of the same
+        // category keep the order of registration
+        self.flavours.sort_by_key(|k| k.category());
         self
     }
 
@@ -99,8 +100,8 @@ impl Scan {
             .flavours
             .iter()
             .find_map(|f| {
-                if f_name.is_some() {
-                    if f_name.as_ref().unwrap() == f.name() {
+                if let Some(n) = &f_name {
+                    if n == f.name() {
                         Some(f.build())
                     } else {
                         None
@@ -157,6 +158,8 @@ impl Scan {
             Err(_) => {
                 let p = flav.dir().as_ref().unwrap().src_path.as_path();
                 error!("Failed to prepare synchronization for {:?}", p);
+                // it will never be processed
+                self.error_done(job);
             }
         }
 
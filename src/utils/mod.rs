@@ -41,6 +41,12 @@ impl From<git2::Error> for SyncError {
     }
 }
 
+impl From<rusqlite::Error> for SyncError {
+    fn from(err: rusqlite::Error) -> Self {
+        SyncError::Failed(format!("sqlite: {}", err))
+    }
+}
+
 impl std::fmt::Display for SyncError {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -121,9 +127,18 @@ pub fn save_dirs_and_files(
 
 /// Remove all directories (recursively) and files from path.
 pub fn rm_dirs_and_files(p: &Path) -> Result<(), SyncError> {
+    rm_dirs_and_files_except(p, &[])
+}
+
+/// Remove all directories (recursively) and files from path but keep
+/// the entries named in 'keep'.
+pub fn rm_dirs_and_files_except(p: &Path, keep: &[&str]) -> Result<(), SyncError> {
     for e in fs::read_dir(p)? {
         match e {
             Ok(e) => {
+                if keep.iter().any(|k| e.file_name() == *k) {
+                    continue;
+                }
                 let t = e.file_type().unwrap();
                 if t.is_file() && e.file_name() != ARGS_FILE && e.file_name() != LOG_FILE {
                     fs::remove_file(e.path().as_path())?;
@@ -138,6 +153,145 @@ pub fn rm_dirs_and_files(p: &Path) -> Result<(), SyncError> {
     Ok(())
 }
 
+/// Append record to manifest file. The fields of a record are
+/// separated by tabs, one record per line. Backslashes, tabs and
+/// newlines in fields are escaped. The record is written at once so
+/// that concurrent jobs can add records to the same manifest.
+pub fn manifest_add(p: &Path, fields: &[&str]) -> Result<(), SyncError> {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|f| {
+            f.replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+        })
+        .collect();
+    let mut f = fs::OpenOptions::new().create(true).append(true).open(p)?;
+    f.write_all(format!("{}\n", fields.join("\t")).as_bytes())?;
+    Ok(())
+}
+
+/// Read all records from manifest file.
+pub fn manifest_read(p: &Path) -> Result<Vec<Vec<String>>, SyncError> {
+    Ok(fs::read_to_string(p)?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.split('\t').map(manifest_unescape).collect())
+        .collect())
+}
+
+/// Undo the escaping of [manifest_add].
+fn manifest_unescape(f: &str) -> String {
+    let mut s = String::with_capacity(f.len());
+    let mut c = f.chars();
+    while let Some(ch) = c.next() {
+        match (ch, ch == '\\') {
+            (_, true) => match c.next() {
+                Some('t') => s.push('\t'),
+                Some('n') => s.push('\n'),
+                Some(n) => s.push(n),
+                None => s.push('\\'),
+            },
+            (ch, false) => s.push(ch),
+        }
+    }
+    s
+}
+
+/// Match glob pattern against path relative to some root. '*', '?'
+/// and classes like '[0-9]' or '[!a]' do not match '/', '**' matches
+/// across directories. Like in
+/// '.gitignore' a pattern without '/' matches any path component,
+/// otherwise it matches the whole path.
+pub fn glob_match(pattern: &str, p: &Path) -> bool {
+    let pattern = pattern.trim_start_matches('/').trim_end_matches('/');
+    if pattern.contains('/') {
+        let s = p.to_string_lossy();
+        glob_match_bytes(pattern.as_bytes(), s.as_bytes())
+    } else {
+        p.iter()
+            .any(|c| glob_match_bytes(pattern.as_bytes(), c.to_string_lossy().as_bytes()))
+    }
+}
+
+fn glob_match_bytes(p: &[u8], s: &[u8]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some(b'*') if p.get(1) == Some(&b'*') => {
+            let rest = &p[2..];
+            // '**/' matches no directory at all
+            if rest.first() == Some(&b'/') && glob_match_bytes(&rest[1..], s) {
+                return true;
+            }
+            (0..=s.len()).any(|i| glob_match_bytes(rest, &s[i..]))
+        }
+        Some(b'*') => {
+            let rest = &p[1..];
+            for i in 0..=s.len() {
+                if glob_match_bytes(rest, &s[i..]) {
+                    return true;
+                }
+                if s.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => match s.first() {
+            Some(c) if *c != b'/' => glob_match_bytes(&p[1..], &s[1..]),
+            _ => false,
+        },
+        Some(b'[') => {
+            let (neg, start) = match p.get(1) {
+                Some(b'!' | b'^') => (true, 2),
+                _ => (false, 1),
+            };
+            // without closing bracket '[' is an ordinary character
+            let end = match p[start..].iter().position(|c| *c == b']') {
+                Some(e) => start + e,
+                None => return s.first() == Some(&b'[') && glob_match_bytes(&p[1..], &s[1..]),
+            };
+            match s.first() {
+                Some(c) if *c != b'/' && glob_class(&p[start..end], *c) != neg => {
+                    glob_match_bytes(&p[end + 1..], &s[1..])
+                }
+                _ => false,
+            }
+        }
+        Some(c) => s.first() == Some(c) && glob_match_bytes(&p[1..], &s[1..]),
+    }
+}
+
+fn glob_class(class: &[u8], c: u8) -> bool {
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == b'-' {
+            if class[i] <= c && c <= class[i + 2] {
+                return true;
+            }
+            i += 3;
+        } else {
+            if class[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+/// Parse size in bytes with optional suffix 'K', 'M' or 'G'.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (n, m) = match s.char_indices().last()? {
+        (i, 'k' | 'K') => (&s[..i], 1 << 10),
+        (i, 'm' | 'M') => (&s[..i], 1 << 20),
+        (i, 'g' | 'G') => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    n.parse::<u64>().ok()?.checked_mul(m)
+}
+
 /// Apply filter to directory entries vector.
 pub fn filter_dir_entries(a: &Vec<PathBuf>, b: &mut Vec<PathBuf>) {
     for e in a {
@@ -184,6 +338,26 @@ pub fn cp_r_d(s: &Path, t: &Path, f: &Path, archive: bool) -> Result<(), SyncErr
     cp_r(s, t, f, archive)
 }
 
+/// Copy symbolic link with relative path and create directory if
+/// needed, an existing entry is replaced.
+pub fn ln_r_d(s: &Path, t: &Path, f: &Path) -> Result<(), SyncError> {
+    if let Some(p) = f.parent() {
+        fs::create_dir_all(t.join(p))?;
+    }
+
+    let tf = t.join(f);
+    let l = fs::read_link(s.join(f))?;
+    match fs::symlink_metadata(&tf) {
+        Ok(m) if m.is_dir() => fs::remove_dir_all(&tf)?,
+        Ok(_) => fs::remove_file(&tf)?,
+        Err(_) => (),
+    }
+
+    trace!("Linking {:?} to {:?}", tf, l);
+    std::os::unix::fs::symlink(l, tf)?;
+    Ok(())
+}
+
 /// Copy file with absolute path.
 pub fn cp(s: &Path, t: &Path, f: &Path, archive: bool) -> Result<(), SyncError> {
     let p = f.strip_prefix(s).unwrap();
@@ -196,6 +370,57 @@ pub fn cp_d(s: &Path, t: &Path, f: &Path, archive: bool) -> Result<(), SyncError
     cp_r_d(s, t, p, archive)
 }
 
+/// Copy all regular files below directory 's' to 't' and create
+/// directories as needed, other entries like symlinks are skipped.
+pub fn cp_tree(s: &Path, t: &Path, archive: bool) -> Result<(), SyncError> {
+    fs::create_dir_all(t)?;
+    for e in fs::read_dir(s)?.flatten() {
+        let ft = e.file_type()?;
+        if ft.is_dir() {
+            cp_tree(&e.path(), &t.join(e.file_name()), archive)?;
+        } else if ft.is_file() {
+            cp_r(s, t, Path::new(&e.file_name()), archive)?;
+        }
+    }
+    Ok(())
+}
+
+/// Update 't' to mirror the regular files below directory 's', files
+/// are only copied if they are missing or differ in size or are
+/// older, entries of 't' that are not in 's' are removed.
+pub fn sync_tree(s: &Path, t: &Path, archive: bool) -> Result<(), SyncError> {
+    fs::create_dir_all(t)?;
+    for e in fs::read_dir(t)?.flatten() {
+        let sp = s.join(e.file_name());
+        let keep = match (fs::symlink_metadata(&sp), e.file_type()?) {
+            (Ok(m), ft) if ft.is_dir() => m.is_dir(),
+            (Ok(m), _) => m.is_file(),
+            (Err(_), _) => false,
+        };
+        if keep {
+            continue;
+        } else if e.file_type()?.is_dir() {
+            fs::remove_dir_all(e.path())?;
+        } else {
+            fs::remove_file(e.path())?;
+        }
+    }
+
+    for e in fs::read_dir(s)?.flatten() {
+        let ft = e.file_type()?;
+        if ft.is_dir() {
+            sync_tree(&e.path(), &t.join(e.file_name()), archive)?;
+        } else if ft.is_file() {
+            let (sm, tm) = (e.metadata()?, fs::metadata(t.join(e.file_name())));
+            if tm.is_ok_and(|tm| tm.len() == sm.len() && tm.modified().ok() >= sm.modified().ok()) {
+                continue;
+            }
+            cp_r(s, t, Path::new(&e.file_name()), archive)?;
+        }
+    }
+    Ok(())
+}
+
 /// Check if a file has changed by comparing the last-modified timestamps.
 pub fn diff(s: &Path, t: &Path, f: &Path) -> bool {
     let p = f.strip_prefix(s).unwrap();
@@ -371,6 +596,42 @@ mod test {
         let _ = fs::remove_dir_all(p);
     }
 
+    #[test]
+    fn test_rm_dirs_and_files_except() {
+        let mut p = path();
+        p.push("rm_dirs_and_files_except");
+        sample_dir(&p);
+        let _ = rm_dirs_and_files_except(&p, &["dir_d", "file_b"]);
+        assert!(p.exists());
+        assert!(!p.join("file_a").exists());
+        assert!(p.join("file_b").exists());
+        assert!(p.join("dir_d").exists());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
+    #[test]
+    fn test_manifest() {
+        let mut p = path();
+        p.push("manifest");
+        create_dir_save(&p, true).expect("Failed to create path");
+        let m = p.join("manifest");
+        manifest_add(&m, &["head", "main"]).expect("Failed to write manifest");
+        manifest_add(&m, &["remote", "origin", "https://example.com/r.git"])
+            .expect("Failed to write manifest");
+        let r = manifest_read(&m).expect("Failed to read manifest");
+        assert!(r.len() == 2);
+        assert!(r[0] == ["head", "main"]);
+        assert!(r[1] == ["remote", "origin", "https://example.com/r.git"]);
+        manifest_add(&m, &["unpushed", "a\tb\\t", "c\nd"]).expect("Failed to write manifest");
+        let r = manifest_read(&m).expect("Failed to read manifest");
+        assert!(r[2] == ["unpushed", "a\tb\\t", "c\nd"]);
+
+        // cleanup
+        let _ = fs::remove_dir_all(p);
+    }
+
     #[test]
     fn test_filter_dir_entries() {
         let p = path();
@@ -463,6 +724,40 @@ mod test {
         let _ = fs::remove_dir_all(p.join("cp_r_d_2"));
     }
 
+    #[test]
+    fn test_cp_tree() {
+        let p = path();
+        sample_dir(&p.join("cp_tree_1"));
+
+        cp_tree(&p.join("cp_tree_1"), &p.join("cp_tree_2"), false).expect("Failed to copy");
+        assert!(p.join("cp_tree_2").join("file_e").exists());
+        assert!(p.join("cp_tree_2").join("dir_d").join("file_b").exists());
+        assert!(p.join("cp_tree_2").join("dir_f").is_dir());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p.join("cp_tree_1"));
+        let _ = fs::remove_dir_all(p.join("cp_tree_2"));
+    }
+
+    #[test]
+    fn test_sync_tree() {
+        let p = path();
+        sample_dir(&p.join("sync_tree_1"));
+
+        sync_tree(&p.join("sync_tree_1"), &p.join("sync_tree_2"), true).expect("Failed to sync");
+        assert!(p.join("sync_tree_2").join("dir_d").join("file_b").exists());
+        let _ = fs::remove_file(p.join("sync_tree_1").join("file_e"));
+        let _ = fs::remove_dir_all(p.join("sync_tree_1").join("dir_d"));
+        sync_tree(&p.join("sync_tree_1"), &p.join("sync_tree_2"), true).expect("Failed to sync");
+        assert!(!p.join("sync_tree_2").join("file_e").exists());
+        assert!(!p.join("sync_tree_2").join("dir_d").exists());
+        assert!(p.join("sync_tree_2").join("file_a").exists());
+
+        // cleanup
+        let _ = fs::remove_dir_all(p.join("sync_tree_1"));
+        let _ = fs::remove_dir_all(p.join("sync_tree_2"));
+    }
+
     #[test]
     fn test_cp() {
         let p = path();
@@ -499,4 +794,39 @@ mod test {
         let _ = fs::remove_dir_all(p.join("cp_d_1"));
         let _ = fs::remove_dir_all(p.join("cp_d_2"));
     }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.env", Path::new("prod.env")));
+        assert!(glob_match("*.env", Path::new("config/prod.env")));
+        assert!(!glob_match("*.env", Path::new("prod.env.bak")));
+        assert!(glob_match(".vscode/**", Path::new(".vscode/settings.json")));
+        assert!(glob_match(".vscode/**", Path::new(".vscode/a/b.json")));
+        assert!(!glob_match(".vscode/**", Path::new("a/.vscode/b.json")));
+        assert!(glob_match(".idea", Path::new(".idea/workspace.xml")));
+        assert!(glob_match("**/local.conf", Path::new("local.conf")));
+        assert!(glob_match(
+            "**/local.conf",
+            Path::new("build/conf/local.conf")
+        ));
+        assert!(glob_match("conf/*.conf", Path::new("conf/local.conf")));
+        assert!(!glob_match("conf/*.conf", Path::new("conf/a/local.conf")));
+        assert!(glob_match("file_?", Path::new("file_a")));
+        assert!(!glob_match("file_?", Path::new("file_ab")));
+        assert!(glob_match("*.so.[0-9]*", Path::new("libfoo.so.1.2")));
+        assert!(!glob_match("*.so.[0-9]*", Path::new("libfoo.so.x")));
+        assert!(glob_match("[Tt]humbs.db", Path::new("thumbs.db")));
+        assert!(glob_match("file_[!b]", Path::new("file_a")));
+        assert!(!glob_match("file_[!b]", Path::new("file_b")));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100"), Some(100));
+        assert_eq!(parse_size("2k"), Some(2048));
+        assert_eq!(parse_size("10M"), Some(10 << 20));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("ten"), None);
+    }
 }